@@ -15,5 +15,5 @@ fn main() {
     let src: PathBuf = std::env::var("CARGO_MANIFEST_DIR").unwrap().into();
     let dst: PathBuf = Path::new(&std::env::var("OUT_DIR").unwrap()).join("built.rs");
 
-    built::write_built_file_with_opts(&options, &src, &dst).expect("Failed to acquire build-time information");
+    built::write_built_file_with_opts(options, &src, &dst).expect("Failed to acquire build-time information");
 }
\ No newline at end of file
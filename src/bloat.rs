@@ -0,0 +1,93 @@
+use std::ffi::OsStr;
+
+/// Size of the fixed part of a directory entry on ext2/3/4, which is followed by the name of the
+/// entry padded to a multiple of four bytes
+const DIRENT_HEADER_SIZE: u64 = 8;
+
+/// Size required for the entries `.` and `..`, which are present in every directory
+const DIRENT_DOT_SIZE: u64 = 2 * 12;
+
+/// A directory is considered bloated when its own size is at least this many times larger than
+/// what is required for its current entries
+pub const BLOAT_FACTOR: u64 = 4;
+
+/// The own size of a directory, i.e. the size of the directory inode rather than the size of the
+/// files in it, and what size that is actually required for its entries.
+///
+/// On some filesystems, such as ext4, a directory never shrinks. A directory that once held a
+/// large number of entries will therefore keep its size even after the entries have been
+/// removed, which makes every lookup in it slow.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DirSize {
+    pub own: u64,
+    pub entries: u64,
+    entries_size: u64,
+    /// Block size of the filesystem of the directory. `None` unless it is on ext2, ext3 or ext4,
+    /// since the required size is only known for their layout of directory entries.
+    pub block_size: Option<u64>,
+}
+
+impl DirSize {
+    #[cfg(test)]
+    pub fn new(own: u64, block_size: Option<u64>) -> DirSize {
+        DirSize {
+            own,
+            block_size,
+            ..DirSize::default()
+        }
+    }
+
+    pub fn add_entry(&mut self, name: &OsStr) {
+        let name_len: u64 = name.len() as u64;
+        self.entries += 1;
+        self.entries_size += DIRENT_HEADER_SIZE + name_len.div_ceil(4) * 4;
+    }
+
+    /// The size that is required to hold the current entries of the directory, rounded up to a
+    /// whole number of blocks, if it is known for the filesystem of the directory
+    pub fn required(&self) -> Option<u64> {
+        let block_size: u64 = self.block_size?;
+        let size: u64 = DIRENT_DOT_SIZE + self.entries_size;
+        let blocks: u64 = size.div_ceil(block_size);
+        Some(blocks.max(1) * block_size)
+    }
+
+    pub fn is_bloated(&self) -> bool {
+        self.required()
+            .is_some_and(|required| self.own >= required * BLOAT_FACTOR)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DirSize;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn test_empty_directory_requires_one_block() {
+        assert_eq!(Some(4096), DirSize::new(4096, Some(4096)).required());
+    }
+
+    #[test]
+    fn test_required_size_of_entries() {
+        let mut dir = DirSize::new(4096, Some(4096));
+        (0..1000).for_each(|_| dir.add_entry(OsStr::new("file_name")));
+        assert_eq!(Some(5 * 4096), dir.required());
+        assert!(!dir.is_bloated());
+    }
+
+    #[test]
+    fn test_large_directory_with_few_entries_is_bloated() {
+        let mut dir = DirSize::new(32 * 1024 * 1024, Some(4096));
+        dir.add_entry(OsStr::new("file_name"));
+        assert!(dir.is_bloated());
+    }
+
+    #[test]
+    fn test_directory_on_other_filesystem_is_not_bloated() {
+        let mut dir = DirSize::new(32 * 1024 * 1024, None);
+        dir.add_entry(OsStr::new("file_name"));
+        assert_eq!(None, dir.required());
+        assert!(!dir.is_bloated());
+    }
+}
@@ -0,0 +1,147 @@
+use crate::cfg::Config;
+use crate::stats::Stats;
+use lazy_static::lazy_static;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Exit code when the search was stopped because its budget was exhausted
+pub const INCOMPLETE_EXIT_CODE: i32 = 3;
+
+static EXHAUSTED: AtomicBool = AtomicBool::new(false);
+static BUDGET: OnceLock<Budget> = OnceLock::new();
+static REASON: OnceLock<Exhausted> = OnceLock::new();
+
+lazy_static! {
+    static ref UNVISITED: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+}
+
+/// How long a search may take and how many entries it may walk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Budget {
+    timeout: Option<Duration>,
+    deadline: Option<Instant>,
+    max_entries: Option<u64>,
+}
+
+/// Why the budget of the search was exhausted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exhausted {
+    Timeout(Duration),
+    MaxEntries(u64),
+}
+
+impl Budget {
+    /// Why the budget is exhausted at `now` after walking `entries`, if it is
+    fn check(&self, now: Instant, entries: u64) -> Option<Exhausted> {
+        if let (Some(timeout), Some(deadline)) = (self.timeout, self.deadline) {
+            if now >= deadline {
+                return Some(Exhausted::Timeout(timeout));
+            }
+        }
+        match self.max_entries {
+            Some(max_entries) if entries >= max_entries => Some(Exhausted::MaxEntries(max_entries)),
+            _ => None,
+        }
+    }
+}
+
+/// Start the time budget of the search, if the search has any budget
+pub fn start(cfg: &Config) {
+    if cfg.timeout.is_none() && cfg.max_entries.is_none() {
+        return;
+    }
+    let budget = Budget {
+        timeout: cfg.timeout,
+        deadline: cfg.timeout.map(|timeout| Instant::now() + timeout),
+        max_entries: cfg.max_entries,
+    };
+    let _ = BUDGET.set(budget);
+}
+
+/// Whether the time or the entries of the search have run out, after which it should stop
+pub fn exhausted() -> bool {
+    if EXHAUSTED.load(Ordering::Relaxed) {
+        return true;
+    }
+    let reason: Option<Exhausted> = BUDGET
+        .get()
+        .and_then(|budget| budget.check(Instant::now(), Stats::get().entries));
+    match reason {
+        Some(reason) => {
+            log::info!("Stopping the search: {:?}", reason);
+            let _ = REASON.set(reason);
+            EXHAUSTED.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Why the search was stopped, if its budget was exhausted
+pub fn reason() -> Option<Exhausted> {
+    REASON.get().copied()
+}
+
+/// Keep track of directories which were not completely walked when the search stopped
+pub fn add_unvisited<I>(paths: I)
+where
+    I: IntoIterator<Item = PathBuf>,
+{
+    UNVISITED.lock().unwrap().extend(paths);
+}
+
+/// The directories which were not completely walked, sorted by path, where directories below
+/// another such directory are left out
+pub fn unvisited() -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = UNVISITED.lock().unwrap().clone();
+    paths.sort();
+    outermost(paths)
+}
+
+fn outermost(sorted: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut outermost: Vec<PathBuf> = Vec::with_capacity(sorted.len());
+    for path in sorted {
+        let below: bool = outermost
+            .last()
+            .is_some_and(|p: &PathBuf| path.starts_with(p));
+        if !below {
+            outermost.push(path);
+        }
+    }
+    outermost
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{outermost, Budget, Exhausted};
+    use std::path::PathBuf;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_budget_is_exhausted() {
+        let start = Instant::now();
+        let budget = Budget {
+            timeout: Some(Duration::from_secs(60)),
+            deadline: Some(start + Duration::from_secs(60)),
+            max_entries: Some(1000),
+        };
+        assert_eq!(None, budget.check(start, 999));
+        assert_eq!(Some(Exhausted::MaxEntries(1000)), budget.check(start, 1000));
+        assert_eq!(
+            Some(Exhausted::Timeout(Duration::from_secs(60))),
+            budget.check(start + Duration::from_secs(60), 0)
+        );
+    }
+
+    #[test]
+    fn test_subdirectories_are_left_out() {
+        let paths: Vec<PathBuf> = ["/a", "/a/b", "/a/c/d", "/ab", "/b/c"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        let expected: Vec<PathBuf> = ["/a", "/ab", "/b/c"].iter().map(PathBuf::from).collect();
+        assert_eq!(expected, outermost(paths));
+    }
+}
@@ -0,0 +1,523 @@
+use crate::entry::Entry;
+use crate::entry_type::EntryType;
+use crate::record::Record;
+use std::collections::HashMap;
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Written at the start of every cache file, and changed whenever the format changes so that a
+/// cache written by another version is never read
+const MAGIC: &[u8] = b"prn-cache-1\n";
+
+const CACHE_FILE: &str = "prune/scan.cache";
+
+/// An entry of a directory, as it was when the directory was last read
+#[derive(Debug, Clone)]
+pub struct CachedEntry {
+    pub name: OsString,
+    pub file_type: EntryType,
+    pub is_symlink: bool,
+    pub record: Record,
+}
+
+impl CachedEntry {
+    /// The cached form of an entry, if its record has been obtained
+    pub fn of(entry: &Entry) -> Option<CachedEntry> {
+        Some(CachedEntry {
+            name: entry.file_name().to_os_string(),
+            file_type: entry.file_type(),
+            is_symlink: entry.path_is_symlink(),
+            record: entry.record().ok()?,
+        })
+    }
+}
+
+/// The entries of a directory, which are valid as long as the directory has not been modified
+/// since it was read
+#[derive(Debug)]
+struct CachedDir {
+    path: PathBuf,
+    follow_links: bool,
+    mtime: Option<SystemTime>,
+    ctime: Option<SystemTime>,
+    entries: Vec<CachedEntry>,
+}
+
+impl CachedDir {
+    fn is_valid_for(&self, dir: &Record, follow_links: bool) -> bool {
+        self.follow_links == follow_links && self.mtime == dir.mtime && self.ctime == dir.ctime
+    }
+}
+
+/// The entries of the directories read during previous searches, keyed by the device and inode of
+/// each directory.
+///
+/// Adding, removing or renaming an entry changes the modification time of the directory, so the
+/// cached entries of a directory whose modification and change time are unchanged can be used
+/// instead of reading the directory and obtaining the metadata of each entry in it. Files which
+/// are modified in place are not noticed this way, so their cached size may be out of date.
+pub struct ScanCache {
+    path: PathBuf,
+    previous: HashMap<(u64, u64), CachedDir>,
+    current: Mutex<HashMap<(u64, u64), CachedDir>>,
+}
+
+impl ScanCache {
+    /// Open the cache in the cache directory of the user, or `None` if there is no such directory
+    pub fn open() -> Option<ScanCache> {
+        let path: PathBuf = cache_file()?;
+        Some(ScanCache::load(path))
+    }
+
+    /// Load the cache from `path`, where a missing or unreadable cache is treated as empty
+    pub fn load(path: PathBuf) -> ScanCache {
+        let previous: HashMap<(u64, u64), CachedDir> = match read_cache(&path) {
+            Ok(dirs) => dirs,
+            Err(err) if err.kind() == ErrorKind::NotFound => HashMap::new(),
+            Err(err) => {
+                log::warn!(
+                    "Ignoring cache {:?}, which could not be read: {}",
+                    path,
+                    err
+                );
+                HashMap::new()
+            }
+        };
+        log::debug!(
+            "Loaded {} directories from cache {:?}",
+            previous.len(),
+            path
+        );
+        ScanCache {
+            path,
+            previous,
+            current: Mutex::default(),
+        }
+    }
+
+    /// The cached entries of a directory, unless the directory has been modified since they were
+    /// cached
+    pub fn lookup(&self, dir: &Record, follow_links: bool) -> Option<&[CachedEntry]> {
+        match self.previous.get(&dir.inode()) {
+            Some(cached) if cached.is_valid_for(dir, follow_links) => Some(&cached.entries),
+            _ => None,
+        }
+    }
+
+    /// Keep all the entries of a directory which was just read, so that they can be used by the
+    /// next search
+    pub fn store(&self, path: &Path, dir: &Record, follow_links: bool, entries: Vec<CachedEntry>) {
+        let cached = CachedDir {
+            path: path.to_path_buf(),
+            follow_links,
+            mtime: dir.mtime,
+            ctime: dir.ctime,
+            entries,
+        };
+        self.current.lock().unwrap().insert(dir.inode(), cached);
+    }
+
+    /// Write the directories read during this search to the cache, together with those that were
+    /// cached before but which were not visited this time
+    pub fn save(&self) -> io::Result<()> {
+        let current = self.current.lock().unwrap();
+        let previous = self
+            .previous
+            .iter()
+            .filter(|(inode, _)| !current.contains_key(inode));
+        let dirs: Vec<(&(u64, u64), &CachedDir)> = current.iter().chain(previous).collect();
+        write_cache(&self.path, &dirs)?;
+        log::debug!("Saved {} directories to cache {:?}", dirs.len(), self.path);
+        Ok(())
+    }
+}
+
+impl fmt::Debug for ScanCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScanCache")
+            .field("path", &self.path)
+            .field("previous", &self.previous.len())
+            .finish()
+    }
+}
+
+/// The outcome of removing stale directories from the cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Collected {
+    pub kept: u64,
+    pub removed: u64,
+    /// Size in bytes of the cache file after the stale directories were removed
+    pub size: u64,
+}
+
+/// Remove the directories from the cache which no longer exist, or which have been modified since
+/// they were cached, and which therefore will never be used again
+pub fn collect_garbage() -> io::Result<Collected> {
+    let path: PathBuf = match cache_file() {
+        Some(path) => path,
+        None => return Ok(Collected::default()),
+    };
+    let dirs: HashMap<(u64, u64), CachedDir> = match read_cache(&path) {
+        Ok(dirs) => dirs,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Collected::default()),
+        Err(err) => {
+            log::warn!(
+                "Removing cache {:?}, which could not be read: {}",
+                path,
+                err
+            );
+            HashMap::new()
+        }
+    };
+    let total: usize = dirs.len();
+    let kept: Vec<(&(u64, u64), &CachedDir)> = dirs
+        .iter()
+        .filter(
+            |(inode, dir)| match Record::stat(&dir.path, dir.follow_links) {
+                Ok(record) => {
+                    record.inode() == **inode && dir.is_valid_for(&record, dir.follow_links)
+                }
+                Err(_) => false,
+            },
+        )
+        .collect();
+    write_cache(&path, &kept)?;
+
+    Ok(Collected {
+        kept: kept.len() as u64,
+        removed: (total - kept.len()) as u64,
+        size: fs::metadata(&path)?.len(),
+    })
+}
+
+/// The cache file in the cache directory of the user, as given by `XDG_CACHE_HOME` or else
+/// `~/.cache`
+pub fn cache_file() -> Option<PathBuf> {
+    let absolute = |var: &str| {
+        let path = PathBuf::from(env::var_os(var)?);
+        path.is_absolute().then_some(path)
+    };
+    let cache_dir: PathBuf =
+        absolute("XDG_CACHE_HOME").or_else(|| absolute("HOME").map(|home| home.join(".cache")))?;
+    Some(cache_dir.join(CACHE_FILE))
+}
+
+fn read_cache(path: &Path) -> io::Result<HashMap<(u64, u64), CachedDir>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut magic: Vec<u8> = vec![0; MAGIC.len()];
+    reader.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "Unknown cache format",
+        ));
+    }
+    let mut dirs: HashMap<(u64, u64), CachedDir> = HashMap::new();
+    while let Some(dev) = read_first_u64(&mut reader)? {
+        let ino: u64 = read_u64(&mut reader)?;
+        let dir = CachedDir {
+            path: PathBuf::from(read_os_str(&mut reader)?),
+            follow_links: read_u8(&mut reader)? != 0,
+            mtime: read_time(&mut reader)?,
+            ctime: read_time(&mut reader)?,
+            entries: (0..read_u64(&mut reader)?)
+                .map(|_| read_entry(&mut reader))
+                .collect::<io::Result<Vec<CachedEntry>>>()?,
+        };
+        dirs.insert((dev, ino), dir);
+    }
+    Ok(dirs)
+}
+
+/// Write the cache to a temporary file first, so that a search which is running at the same time
+/// never sees a partially written cache
+fn write_cache(path: &Path, dirs: &[(&(u64, u64), &CachedDir)]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp: PathBuf = path.with_extension(format!("tmp.{}", std::process::id()));
+    let mut writer = BufWriter::new(File::create(&tmp)?);
+    writer.write_all(MAGIC)?;
+    for ((dev, ino), dir) in dirs {
+        write_u64(&mut writer, *dev)?;
+        write_u64(&mut writer, *ino)?;
+        write_os_str(&mut writer, dir.path.as_os_str())?;
+        write_u8(&mut writer, dir.follow_links as u8)?;
+        write_time(&mut writer, dir.mtime)?;
+        write_time(&mut writer, dir.ctime)?;
+        write_u64(&mut writer, dir.entries.len() as u64)?;
+        for entry in &dir.entries {
+            write_entry(&mut writer, entry)?;
+        }
+    }
+    writer.into_inner().map_err(|err| err.into_error())?;
+    fs::rename(&tmp, path)
+}
+
+fn write_entry<W: Write>(w: &mut W, entry: &CachedEntry) -> io::Result<()> {
+    let record: &Record = &entry.record;
+    write_os_str(w, &entry.name)?;
+    write_entry_type(w, Some(entry.file_type))?;
+    write_u8(w, entry.is_symlink as u8)?;
+    write_u64(w, record.size)?;
+    write_u64(w, record.blocks)?;
+    write_time(w, record.mtime)?;
+    write_time(w, record.atime)?;
+    write_time(w, record.ctime)?;
+    write_time(w, record.btime)?;
+    write_u64(w, record.ino)?;
+    write_u64(w, record.dev)?;
+    write_u64(w, record.nlink)?;
+    write_u64(w, u64::from(record.uid))?;
+    write_u64(w, u64::from(record.mode))?;
+    write_entry_type(w, record.entry_type)
+}
+
+fn read_entry<R: Read>(r: &mut R) -> io::Result<CachedEntry> {
+    let name: OsString = read_os_str(r)?;
+    let file_type: EntryType = read_entry_type(r)?.ok_or_else(invalid)?;
+    let is_symlink: bool = read_u8(r)? != 0;
+    let record = Record {
+        size: read_u64(r)?,
+        blocks: read_u64(r)?,
+        mtime: read_time(r)?,
+        atime: read_time(r)?,
+        ctime: read_time(r)?,
+        btime: read_time(r)?,
+        ino: read_u64(r)?,
+        dev: read_u64(r)?,
+        nlink: read_u64(r)?,
+        uid: u32::try_from(read_u64(r)?).map_err(|_| invalid())?,
+        mode: u32::try_from(read_u64(r)?).map_err(|_| invalid())?,
+        entry_type: read_entry_type(r)?,
+    };
+    Ok(CachedEntry {
+        name,
+        file_type,
+        is_symlink,
+        record,
+    })
+}
+
+fn invalid() -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, "Corrupt cache")
+}
+
+fn write_u8<W: Write>(w: &mut W, value: u8) -> io::Result<()> {
+    w.write_all(&[value])
+}
+
+fn read_u8<R: Read>(r: &mut R) -> io::Result<u8> {
+    let mut buf = [0u8; 1];
+    r.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn write_u64<W: Write>(w: &mut W, value: u64) -> io::Result<()> {
+    w.write_all(&value.to_le_bytes())
+}
+
+fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+/// Read a number at the start of a directory, or `None` at the end of the cache
+fn read_first_u64<R: Read>(r: &mut R) -> io::Result<Option<u64>> {
+    let mut buf = [0u8; 8];
+    match r.read(&mut buf[..1])? {
+        0 => Ok(None),
+        _ => {
+            r.read_exact(&mut buf[1..])?;
+            Ok(Some(u64::from_le_bytes(buf)))
+        }
+    }
+}
+
+/// A time is written as a flag for whether it is present, followed by the seconds and nanoseconds
+/// since the Unix epoch
+fn write_time<W: Write>(w: &mut W, time: Option<SystemTime>) -> io::Result<()> {
+    match time.and_then(|t| t.duration_since(UNIX_EPOCH).ok()) {
+        Some(since_epoch) => {
+            write_u8(w, 1)?;
+            write_u64(w, since_epoch.as_secs())?;
+            write_u64(w, u64::from(since_epoch.subsec_nanos()))
+        }
+        None => write_u8(w, 0),
+    }
+}
+
+fn read_time<R: Read>(r: &mut R) -> io::Result<Option<SystemTime>> {
+    if read_u8(r)? == 0 {
+        return Ok(None);
+    }
+    let secs: u64 = read_u64(r)?;
+    let nanos = u32::try_from(read_u64(r)?).map_err(|_| invalid())?;
+    Ok(UNIX_EPOCH.checked_add(Duration::new(secs, nanos)))
+}
+
+/// A type is written as its indicator, such as `d` for a directory, or 0 if it is not known
+fn write_entry_type<W: Write>(w: &mut W, entry_type: Option<EntryType>) -> io::Result<()> {
+    match entry_type {
+        Some(entry_type) => write_u8(w, entry_type.indicator() as u8),
+        None => write_u8(w, 0),
+    }
+}
+
+fn read_entry_type<R: Read>(r: &mut R) -> io::Result<Option<EntryType>> {
+    match read_u8(r)? {
+        0 => Ok(None),
+        indicator => {
+            let indicator = char::from(indicator).to_string();
+            EntryType::from_str(&indicator)
+                .map(Some)
+                .map_err(|_| invalid())
+        }
+    }
+}
+
+fn write_os_str<W: Write>(w: &mut W, s: &OsStr) -> io::Result<()> {
+    let bytes: &[u8] = os_str_bytes(s);
+    write_u64(w, bytes.len() as u64)?;
+    w.write_all(bytes)
+}
+
+/// The length is read from the cache, so the bytes are read into a buffer which grows as they are
+/// read rather than allocated up front, in case the cache is corrupt
+fn read_os_str<R: Read>(r: &mut R) -> io::Result<OsString> {
+    let len: u64 = read_u64(r)?;
+    let mut bytes: Vec<u8> = Vec::new();
+    r.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(ErrorKind::UnexpectedEof.into());
+    }
+    os_string_from(bytes)
+}
+
+#[cfg(unix)]
+fn os_str_bytes(s: &OsStr) -> &[u8] {
+    use std::os::unix::ffi::OsStrExt;
+    s.as_bytes()
+}
+
+#[cfg(not(unix))]
+fn os_str_bytes(s: &OsStr) -> &[u8] {
+    s.as_encoded_bytes()
+}
+
+#[cfg(unix)]
+fn os_string_from(bytes: Vec<u8>) -> io::Result<OsString> {
+    use std::os::unix::ffi::OsStringExt;
+    Ok(OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn os_string_from(bytes: Vec<u8>) -> io::Result<OsString> {
+    String::from_utf8(bytes)
+        .map(OsString::from)
+        .map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_u64, CachedEntry, ScanCache, MAGIC};
+    use crate::cfg::Config;
+    use crate::entry_type::EntryType;
+    use crate::record::Record;
+    use crate::size::Size;
+    use crate::{walk_dirs, walk_dirs_cached};
+    use std::ffi::OsString;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+
+    fn cache_path(name: &str) -> PathBuf {
+        let dir: PathBuf = std::env::temp_dir().join(format!("prn-test-{}", std::process::id()));
+        dir.join(name)
+    }
+
+    #[test]
+    fn test_cached_entries_are_saved_and_loaded() {
+        let path: PathBuf = cache_path("saved.cache");
+        let dir: Record = Record::stat(Path::new("test_dirs"), false).unwrap();
+        let file: Record = Record::stat(Path::new("test_dirs/file0"), false).unwrap();
+        let entry = CachedEntry {
+            name: OsString::from("file0"),
+            file_type: EntryType::File,
+            is_symlink: false,
+            record: file,
+        };
+
+        let cache = ScanCache::load(path.clone());
+        assert!(cache.lookup(&dir, false).is_none());
+        cache.store(Path::new("test_dirs"), &dir, false, vec![entry]);
+        cache.save().unwrap();
+
+        let cache = ScanCache::load(path.clone());
+        let entries: &[CachedEntry] = cache.lookup(&dir, false).unwrap();
+        assert_eq!(1, entries.len());
+        assert_eq!(OsString::from("file0"), entries[0].name);
+        assert_eq!(file, entries[0].record);
+        assert!(cache.lookup(&dir, true).is_none());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_modified_directory_is_not_used() {
+        let path: PathBuf = cache_path("modified.cache");
+        let dir: Record = Record::stat(Path::new("test_dirs"), false).unwrap();
+        let cache = ScanCache::load(path);
+        cache.store(Path::new("test_dirs"), &dir, false, Vec::new());
+        cache.save().unwrap();
+
+        let modified = Record {
+            mtime: dir.mtime.map(|t| t + std::time::Duration::from_secs(1)),
+            ..dir
+        };
+        let cache = ScanCache::load(cache_path("modified.cache"));
+        assert!(cache.lookup(&dir, false).is_some());
+        assert!(cache.lookup(&modified, false).is_none());
+        fs::remove_file(cache_path("modified.cache")).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_cache_is_ignored() {
+        let path: PathBuf = cache_path("corrupt.cache");
+        let mut corrupt: Vec<u8> = MAGIC.to_vec();
+        [1, 2, u64::MAX]
+            .iter()
+            .for_each(|n| write_u64(&mut corrupt, *n).unwrap());
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, corrupt).unwrap();
+
+        let cache = ScanCache::load(path.clone());
+        assert!(cache.previous.is_empty());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_directories_are_found_with_the_cache() {
+        let path: PathBuf = cache_path("dirs.cache");
+        let cfg: Config = Config::default()
+            .with_path("test_dirs")
+            .with_dirs()
+            .with_min_size(Size::Byte(0));
+        let cache = || Some(Arc::new(ScanCache::load(path.clone())));
+        let uncached: (u64, u64) = walk_dirs(&cfg);
+        assert!(!path.exists());
+
+        // The first search fills the cache, and the second one reads from it
+        assert_eq!(uncached, walk_dirs_cached(&cfg, cache()));
+        assert!(path.exists());
+        assert_eq!(uncached, walk_dirs_cached(&cfg, cache()));
+        fs::remove_file(path).unwrap();
+    }
+}
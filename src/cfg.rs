@@ -1,6 +1,12 @@
-use crate::{duration::parse_duration, size::Size};
+use crate::entry_type::EntryType;
+use crate::filesystem::fs_stats;
+use crate::mounts::{FsTypeFilter, Mount};
+use crate::parse::parse_percent;
+use crate::{duration::parse_duration, size::Size, usage::Measure};
 use itertools::Itertools;
 use regex::Regex;
+use std::io;
+use std::num::NonZeroU32;
 use std::path::Path;
 use std::time::Duration;
 use std::{path::PathBuf, str::FromStr};
@@ -21,6 +27,9 @@ pub struct Config {
     #[structopt(parse(from_os_str), default_value = ".")]
     paths: Vec<PathBuf>,
 
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+
     /// Print debug information
     ///
     /// Print debug information about current build for binary, useful for when an issue is
@@ -30,16 +39,261 @@ pub struct Config {
 
     ///Search for directories
     ///
-    /// Search for directories instead of files
+    /// Search for directories instead of files. Each directory is printed as soon as it and all its
+    /// subdirectories have been walked, so a directory comes after its subdirectories.
     #[structopt(short = "R", long)]
     dirs: bool,
 
+    /// Analyze extents and fragmentation
+    ///
+    /// Report the number of extents, bytes shared with other files (such as snapshots or clones),
+    /// unwritten (preallocated) bytes and holes for each file found. Files are listed with the most
+    /// fragmented file first. Only supported on Linux.
+    #[structopt(short = "e", long, conflicts_with = "dirs")]
+    extents: bool,
+
+    /// Count entries in directories
+    ///
+    /// Search for directories with the largest number of entries (files, directories and other
+    /// types of files) rather than the largest size. Each entry occupies an inode, so this is useful
+    /// when a filesystem is running out of inodes. Only the entries which match --pattern, --type
+    /// and the modification time filters are counted.
+    #[structopt(short = "c", long, conflicts_with_all = &["dirs", "extents"])]
+    count: bool,
+
+    /// Report slack in directories
+    ///
+    /// Search for directories where the most space is wasted on slack, i.e. space that is allocated
+    /// on disk but which is not used by the files, such as the unused part of the last block of each
+    /// file. Also shows the distribution of file sizes in relation to the block size of the
+    /// filesystem.
+    #[structopt(long, conflicts_with_all = &["dirs", "extents", "count"])]
+    slack: bool,
+
+    /// Find deleted files that are still open
+    ///
+    /// Find files that have been deleted but which are still held open by a process, and
+    /// therefore still occupy space on disk. Only files that were located in the given path(s)
+    /// are listed, together with the process holding them open. Files in memory, such as on tmpfs,
+    /// are skipped unless their filesystem type is given by --fs-type. In plumbing mode, process
+    /// names are percent-encoded as with --open-by. Only supported on Linux.
+    #[structopt(
+        long = "deleted-open",
+        conflicts_with_all = &["dirs", "extents", "count", "slack"]
+    )]
+    deleted_open: bool,
+
+    /// Find bloated directories
+    ///
+    /// Search for directories whose own size, i.e. the size of the directory itself rather than
+    /// the files in it, is far larger than what is required for the entries currently in it. On
+    /// filesystems such as ext4 a directory never shrinks, so a directory that once held a large
+    /// number of files remains large and slow to look up files in, even after the files are
+    /// removed. Only directories on ext2, ext3 and ext4 are checked.
+    #[structopt(
+        long = "bloated",
+        conflicts_with_all = &["dirs", "extents", "count", "slack", "deleted-open"]
+    )]
+    bloated: bool,
+
+    /// Find broken symbolic links
+    ///
+    /// List symbolic links which point to a file or directory that does not exist, together with
+    /// the path they point to.
+    #[structopt(
+        long = "broken-links",
+        conflicts_with_all = &["dirs", "extents", "count", "slack", "deleted-open", "bloated"]
+    )]
+    broken_links: bool,
+
+    /// Follow symbolic links
+    ///
+    /// Follow symbolic links to directories and files, rather than ignoring them. Links that point
+    /// back to one of their own parent directories are not followed, and a file or directory which
+    /// is the target of several links is only counted once.
+    #[structopt(short = "L", long = "follow")]
+    pub follow_links: bool,
+
     /// Current filesystem only
     ///
     /// Only search for files in the same filesystem for the given path(s), or the current file
     /// system if no path is given.
     #[structopt(short = "x", long = "filesystem")]
-    pub only_local_fs: bool,
+    only_local_fs: bool,
+
+    /// Only search filesystems fuller than this
+    ///
+    /// Instead of searching the given path(s), search all local filesystems where more than this
+    /// percentage of the space is used, such as `85%`. Implies --filesystem.
+    #[structopt(long = "fuller-than", parse(try_from_str = parse_percent))]
+    pub fuller_than: Option<f64>,
+
+    /// Only search filesystems of these types
+    ///
+    /// Only descend into mounted filesystems of these types, given as a comma separated list such as
+    /// `ext4,xfs`. This overrides the default exclusion of pseudo filesystems, such as proc and
+    /// sysfs.
+    #[structopt(long = "fs-type", use_delimiter = true)]
+    pub fs_types: Vec<String>,
+
+    /// Exclude filesystems of these types
+    ///
+    /// Do not descend into mounted filesystems of these types, given as a comma separated list such
+    /// as `tmpfs,squashfs`. Pseudo filesystems, such as proc and sysfs, are always excluded unless
+    /// --fs-type is given.
+    #[structopt(long = "exclude-fs-type", use_delimiter = true)]
+    pub exclude_fs_types: Vec<String>,
+
+    /// Include remote filesystems
+    ///
+    /// Descend into mounted network filesystems, such as NFS, CIFS and sshfs, which are skipped by
+    /// default. Each remote filesystem is abandoned and reported as incomplete if it does not
+    /// respond within the time given by --remote-timeout, which is done on stderr in plumbing mode.
+    #[structopt(long = "include-remote")]
+    pub include_remote: bool,
+
+    /// Timeout for remote filesystems
+    ///
+    /// Abandon a remote filesystem if it has not responded within this time, such as `30s` for 30
+    /// seconds or `2m` for two minutes. Only used together with --include-remote.
+    #[structopt(
+        long = "remote-timeout",
+        default_value = "30s",
+        parse(try_from_str = parse_duration)
+    )]
+    pub remote_timeout: Duration,
+
+    /// Number of threads to walk with
+    ///
+    /// Walk directories with this many threads in parallel, which is faster when walking is limited
+    /// by the latency of the filesystem rather than its bandwidth, such as on network filesystems
+    /// and fast disks. A value of 0 uses one thread per available CPU. Files and directories are
+    /// listed in no particular order when more than one thread is used, unless --sort is given.
+    #[structopt(short = "j", long = "threads", default_value = "1")]
+    threads: usize,
+
+    /// Estimate sizes from a sample of the files
+    ///
+    /// Only obtain the size of this percentage of the files in each directory, such as `10%`, and
+    /// estimate the size of each directory from the files sampled. Every directory is still read,
+    /// and directories with few files are not sampled. Sizes are given with a 95% confidence
+    /// interval. Only used when searching for directories.
+    #[structopt(long = "sample", requires = "dirs", parse(try_from_str = parse_percent))]
+    pub sample: Option<f64>,
+
+    /// Explore the largest directories first
+    ///
+    /// First read the directories at the top of the tree, and then read the directories which look
+    /// the largest before any others, based on the size of the files found so far, the number of
+    /// links of each directory and the scan cache. The largest directories found so far are
+    /// printed to stderr every --ranking-interval, so that the search can be stopped as soon as
+    /// the picture is clear enough. Only used when searching for directories.
+    #[structopt(long = "biggest-first", requires = "dirs")]
+    pub biggest_first: bool,
+
+    /// Interval between rankings
+    ///
+    /// How often to print the largest directories found so far, such as `10s` for ten seconds.
+    /// Only used together with --biggest-first.
+    #[structopt(
+        long = "ranking-interval",
+        default_value = "10s",
+        parse(try_from_str = parse_duration)
+    )]
+    pub ranking_interval: Duration,
+
+    /// Read entries in inode order
+    ///
+    /// Read all the entries of each directory and sort them by inode number before obtaining their
+    /// metadata, and descend into subdirectories in the same order. On spinning disks this avoids
+    /// much of the seeking caused by visiting entries in the order they are stored in the
+    /// directory.
+    #[structopt(long = "inode-order")]
+    pub inode_order: bool,
+
+    /// Limit the rate of calls to stat
+    ///
+    /// Make at most this many calls to `stat` per second, over all threads, so that a search on a
+    /// busy host leaves most of the I/O capacity to other processes. The time spent waiting is
+    /// reported when done.
+    #[structopt(long = "stat-rate")]
+    pub stat_rate: Option<NonZeroU32>,
+
+    /// Limit the rate of directories read
+    ///
+    /// Read at most this many directories per second, over all threads. The time spent waiting is
+    /// reported when done.
+    #[structopt(long = "dir-rate")]
+    pub dir_rate: Option<NonZeroU32>,
+
+    /// Pause while the load is high
+    ///
+    /// Pause the search while the load average over the last minute is above this value, such as
+    /// the number of CPUs of the host. The time spent waiting is reported when done.
+    #[structopt(long = "max-load")]
+    pub max_load: Option<f64>,
+
+    /// Run with low priority
+    ///
+    /// Use the lowest CPU priority (niceness 19) and, on Linux, the idle I/O scheduling class, so
+    /// that the search only gets CPU time and disk access which no other process wants.
+    #[structopt(long = "low-priority")]
+    pub low_priority: bool,
+
+    /// Stop the search after this time
+    ///
+    /// Stop walking once the search has taken this long, such as `10m` for ten minutes, and print
+    /// the results found so far together with the directories which were not completely walked.
+    /// The exit code is 3 when the search was stopped.
+    #[structopt(long = "timeout", parse(try_from_str = parse_duration))]
+    pub timeout: Option<Duration>,
+
+    /// Stop the search after this many entries
+    ///
+    /// Stop walking once this many files and directories have been walked, and print the results
+    /// found so far together with the directories which were not completely walked. The exit code
+    /// is 3 when the search was stopped.
+    #[structopt(long = "max-entries")]
+    pub max_entries: Option<u64>,
+
+    /// Sort files or directories found
+    ///
+    /// Sort files or directories found by `size`, with the largest first, or by `path`. They are
+    /// otherwise listed in the order they are found. When sorting, --limit applies to the sorted
+    /// files or directories. Can not be combined with --count, --slack, --bloated, --extents or
+    /// --deleted-open, which list their results in an order of their own.
+    #[structopt(
+        long = "sort",
+        conflicts_with_all = &["count", "slack", "bloated", "extents", "deleted-open"]
+    )]
+    pub sort: Option<Sort>,
+
+    /// Do not use the scan cache
+    ///
+    /// When searching for directories, the entries of each directory are cached under
+    /// `$XDG_CACHE_HOME/prune`, and the next search reuses the cached entries of directories which
+    /// have not been modified since. Files which are modified in place do not modify their
+    /// directory, so their size may be out of date in the cache. This reads every directory again
+    /// and leaves the cache unchanged.
+    #[structopt(long = "no-cache")]
+    pub no_cache: bool,
+
+    /// Show progress
+    ///
+    /// Show the number of entries and directories walked, the size of the files walked, the
+    /// number of results found, the current directory and the rate on stderr while searching,
+    /// which is done by default when stderr is a terminal. When whole filesystems are searched,
+    /// the share of the search which is done and the time left are estimated from the used inodes
+    /// and bytes of the filesystems.
+    #[structopt(long = "progress")]
+    pub progress: bool,
+
+    /// Report statistics
+    ///
+    /// Print the number of entries and directories walked, the number of calls to `stat` and the
+    /// time spent, to stderr when done.
+    #[structopt(long = "stats")]
+    pub stats: bool,
 
     /// Use plumbing mode
     ///
@@ -48,6 +302,61 @@ pub struct Config {
     #[structopt(short = "P", long = "plumbing")]
     pub plumbing_mode: bool,
 
+    /// Show processes which have files open
+    ///
+    /// Annotate each file found with the processes that currently have it open, and whether they
+    /// have it open for writing or only for reading. Only used when searching for files, and only
+    /// supported on Linux. In plumbing mode, commas, spaces, colons and percent signs in the names
+    /// of the processes are percent-encoded, such as `%2C` for a comma.
+    #[structopt(
+        long = "open-by",
+        conflicts_with_all = &["dirs", "extents", "count", "slack", "deleted-open", "bloated", "broken-links"]
+    )]
+    open_by: bool,
+
+    /// Only list files which are open
+    ///
+    /// Only list files which are currently open by at least one process. Implies --open-by, and is
+    /// only used when searching for files.
+    #[structopt(
+        long = "only-open",
+        conflicts_with_all = &["only-closed", "dirs", "extents", "count", "slack", "deleted-open", "bloated", "broken-links"]
+    )]
+    pub only_open: bool,
+
+    /// Only list files which are not open
+    ///
+    /// Only list files which are currently not open by any process. Implies --open-by, and is only
+    /// used when searching for files.
+    #[structopt(
+        long = "only-closed",
+        conflicts_with_all = &["dirs", "extents", "count", "slack", "deleted-open", "bloated", "broken-links"]
+    )]
+    pub only_closed: bool,
+
+    /// Use disk usage instead of apparent size
+    ///
+    /// Use the number of bytes actually allocated on disk for a file rather than its apparent
+    /// size, when filtering, aggregating and summarizing. This gives a more accurate picture for
+    /// sparse files and for directories with a lot of small files.
+    #[structopt(short = "u", long = "disk-usage")]
+    disk_usage: bool,
+
+    /// Show both apparent size and disk usage
+    ///
+    /// Print both the apparent size and the disk usage for each file or directory, side by side.
+    /// Files where only a small part of the apparent size is allocated on disk are flagged as
+    /// sparse.
+    #[structopt(short = "b", long = "both-sizes")]
+    pub both_sizes: bool,
+
+    /// Show the own size of directories
+    ///
+    /// Print the size of each directory itself, rather than the files in it, after its combined
+    /// size. Only used when searching for directories with --dirs.
+    #[structopt(long = "own-size", requires = "dirs")]
+    pub own_size: bool,
+
     /// Filter files by regex pattern
     ///
     /// Descend and search for files or directories in directories with a max depth of this value.
@@ -77,6 +386,15 @@ pub struct Config {
     #[structopt(short = "M", long = "max-mod-time", parse(try_from_str = parse_duration))]
     pub max_age: Option<Duration>,
 
+    /// Only include entries of these types
+    ///
+    /// Only include entries of these types, given as a comma separated list of file, dir, symlink,
+    /// fifo, socket, block and char. By default only regular files are included, or entries of all
+    /// types when counting entries. When searching for directories, the size of a directory is the
+    /// combined size of the entries of these types in it.
+    #[structopt(short = "t", long = "type", use_delimiter = true)]
+    pub types: Vec<EntryType>,
+
     /// Filter files by regex pattern
     ///
     /// Only include and count files matching the regular expression
@@ -101,8 +419,23 @@ pub struct Config {
         parse(try_from_str)
     )]
     min_size: Size,
+
+    /// Minimum number of entries
+    ///
+    /// Only show directories which contain at least this many entries, including entries in
+    /// subdirectories. Only used when counting entries with --count.
+    #[structopt(short = "n", long = "min-entries", default_value = "1000")]
+    pub min_entries: u64,
+
+    /// Minimum slack
+    ///
+    /// Only show directories which have at least this much slack. Uses the same units as --size.
+    /// Only used when searching for slack with --slack.
+    #[structopt(long = "min-slack", default_value = "0", parse(try_from_str))]
+    min_slack: Size,
 }
 
+#[allow(dead_code)]
 struct Verbosity(u8);
 
 impl FromStr for Verbosity {
@@ -120,11 +453,50 @@ impl FromStr for Verbosity {
 }
 
 impl Config {
+    #[cfg(test)]
     pub fn with_path<T: Into<PathBuf>>(mut self, path: T) -> Self {
         self.paths.push(path.into());
         self
     }
 
+    #[cfg(test)]
+    pub fn with_fs_types(mut self, fs_types: &[&str]) -> Self {
+        self.fs_types = fs_types.iter().map(|t| t.to_string()).collect();
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with_follow_links(mut self, follow_links: bool) -> Self {
+        self.follow_links = follow_links;
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with_dirs(mut self) -> Self {
+        self.dirs = true;
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with_min_size(mut self, min_size: Size) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with_inode_order(mut self) -> Self {
+        self.inode_order = true;
+        self
+    }
+
+    #[cfg(test)]
+    #[allow(dead_code)]
     pub fn with_limit(mut self, limit: Option<usize>) -> Self {
         self.limit = limit;
         self
@@ -134,24 +506,152 @@ impl Config {
         self.min_size.as_bytes()
     }
 
+    pub fn min_slack_bytes(&self) -> u64 {
+        self.min_slack.as_bytes()
+    }
+
     pub fn max_depth(&self) -> usize {
         self.depth.unwrap_or(usize::MAX)
     }
 
+    pub fn open_by(&self) -> bool {
+        self.open_by || self.only_open || self.only_closed
+    }
+
+    pub fn measure(&self) -> Measure {
+        if self.disk_usage {
+            Measure::Allocated
+        } else {
+            Measure::Apparent
+        }
+    }
+
     pub fn mode(&self) -> Mode {
         if self.dirs {
             Mode::Dir
+        } else if self.extents {
+            Mode::Extents
+        } else if self.count {
+            Mode::Count
+        } else if self.slack {
+            Mode::Slack
+        } else if self.deleted_open {
+            Mode::DeletedOpen
+        } else if self.bloated {
+            Mode::Bloated
+        } else if self.broken_links {
+            Mode::BrokenLinks
         } else {
             Mode::File
         }
     }
 
+    /// Whether the entry of each directory is produced after the entries below it, so that the
+    /// size of each directory can be printed as soon as it is known
+    pub fn contents_first(&self) -> bool {
+        matches!(self.mode(), Mode::Dir)
+    }
+
+    /// Whether the scan cache is used, which it only is when searching for directories
+    pub fn use_cache(&self) -> bool {
+        matches!(self.mode(), Mode::Dir) && !self.no_cache
+    }
+
+    /// Whether the search is stopped when it takes too long or walks too many entries
+    pub fn has_budget(&self) -> bool {
+        self.timeout.is_some() || self.max_entries.is_some()
+    }
+
+    pub fn threads(&self) -> usize {
+        match self.threads {
+            0 => std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            threads => threads,
+        }
+    }
+
+    pub fn only_local_fs(&self) -> bool {
+        self.only_local_fs || self.fuller_than.is_some()
+    }
+
     pub fn paths(&self) -> Vec<PathBuf> {
-        self.paths
+        if let Some(threshold) = self.fuller_than {
+            return self.full_filesystems(threshold);
+        }
+        let paths: Vec<PathBuf> = self
+            .paths
             .clone()
             .into_iter()
             .sorted()
-            .filter(|p| Config::filter(&p))
+            .filter(|p| Config::filter(p))
+            .map(|p| Config::absolute(&p))
+            .collect_vec();
+
+        Config::outermost(paths)
+    }
+
+    /// The absolute form of a path to search, without any `.` or `..` components or symbolic
+    /// links. If the path itself is a symbolic link it is kept as is, so that the link is still
+    /// what is searched. Since all paths found when searching are below these paths, they never
+    /// have to be made canonical when printed.
+    fn absolute(path: &Path) -> PathBuf {
+        let absolute: io::Result<PathBuf> = match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) if path.is_symlink() => {
+                let parent: &Path = match parent.as_os_str().is_empty() {
+                    true => Path::new("."),
+                    false => parent,
+                };
+                parent.canonicalize().map(|parent| parent.join(name))
+            }
+            _ => path.canonicalize(),
+        };
+        absolute.unwrap_or_else(|_| path.to_path_buf())
+    }
+
+    /// Remove paths which are nested inside another path, or which are the same as another path,
+    /// so that no file is searched more than once
+    fn outermost(paths: Vec<PathBuf>) -> Vec<PathBuf> {
+        let canonical: Vec<PathBuf> = paths
+            .iter()
+            .map(|p| p.canonicalize().unwrap_or_else(|_| p.clone()))
+            .collect();
+
+        paths
+            .into_iter()
+            .enumerate()
+            .filter(|(i, path)| {
+                let nested: bool = canonical.iter().enumerate().any(|(j, other)| {
+                    let path: &PathBuf = &canonical[*i];
+                    j != *i && path.starts_with(other) && (path != other || j < *i)
+                });
+                if nested {
+                    log::debug!("Skipping {:?}, since it is included in another path", path);
+                }
+                !nested
+            })
+            .map(|(_, path)| path)
+            .collect_vec()
+    }
+
+    /// Mount points of the local filesystems where more than `threshold` percent of the space is
+    /// used
+    fn full_filesystems(&self, threshold: f64) -> Vec<PathBuf> {
+        let fs_types: FsTypeFilter = self.into();
+        fs_types
+            .real_mounts()
+            .into_iter()
+            .filter(|m: &&Mount| !m.is_remote())
+            .filter(|m: &&Mount| match fs_stats(&m.mount_point) {
+                Ok(stats) => stats.percent_used() > threshold,
+                Err(err) => {
+                    log::warn!("Unable to obtain usage of {:?}: {}", m.mount_point, err);
+                    false
+                }
+            })
+            .map(|m: &Mount| m.mount_point.clone())
+            .sorted()
+            .dedup()
             .collect_vec()
     }
 
@@ -167,23 +667,104 @@ impl Default for Config {
     fn default() -> Self {
         Config {
             paths: Vec::with_capacity(1),
+            command: None,
             print_dbg: false,
             dirs: false,
+            extents: false,
+            count: false,
+            slack: false,
+            deleted_open: false,
+            bloated: false,
+            broken_links: false,
+            follow_links: false,
             only_local_fs: true,
+            fuller_than: None,
+            fs_types: Vec::with_capacity(0),
+            exclude_fs_types: Vec::with_capacity(0),
+            include_remote: false,
+            remote_timeout: Duration::from_secs(30),
+            threads: 1,
+            sample: None,
+            biggest_first: false,
+            ranking_interval: Duration::from_secs(10),
+            inode_order: false,
+            stat_rate: None,
+            dir_rate: None,
+            max_load: None,
+            low_priority: false,
+            timeout: None,
+            max_entries: None,
+            sort: None,
+            no_cache: true,
+            progress: false,
+            stats: false,
             plumbing_mode: true,
+            open_by: false,
+            only_open: false,
+            only_closed: false,
+            disk_usage: false,
+            both_sizes: false,
+            own_size: false,
             depth: None,
             limit: None,
             min_age: None,
             max_age: None,
+            types: Vec::with_capacity(0),
             pattern: None,
             verbosity_level: 0,
             min_size: Size::Megabyte(100),
+            min_entries: 1000,
+            min_slack: Size::Byte(0),
         }
     }
 }
 
+#[derive(StructOpt, Debug)]
+pub enum Command {
+    /// List mounted filesystems
+    ///
+    /// List the size, usage and inode usage of all mounted filesystems, except pseudo filesystems
+    /// and remote filesystems unless --include-remote is given.
+    Mounts,
+    /// Manage the scan cache
+    Cache(CacheCommand),
+}
+
+#[derive(StructOpt, Debug)]
+pub enum CacheCommand {
+    /// Remove stale directories from the scan cache
+    ///
+    /// Remove the directories from the scan cache which no longer exist, or which have been
+    /// modified since they were cached.
+    Gc,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum Mode {
     File,
     Dir,
+    Extents,
+    DeletedOpen,
+    Count,
+    Slack,
+    Bloated,
+    BrokenLinks,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sort {
+    Size,
+    Path,
+}
+
+impl FromStr for Sort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "size" => Ok(Sort::Size),
+            "path" => Ok(Sort::Path),
+            _ => Err(format!("Invalid sort order '{}', expected size or path", s)),
+        }
+    }
 }
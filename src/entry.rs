@@ -0,0 +1,158 @@
+use crate::entry_type::EntryType;
+use crate::record::Record;
+use crate::sample::Sampled;
+use std::cell::OnceCell;
+use std::ffi::OsStr;
+use std::fs::FileType;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// An entry found when walking a filesystem, regardless of which walker that found it. The record
+/// of the entry is obtained at most once, when it is first needed, unless the walker already
+/// obtained it.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    path: PathBuf,
+    file_type: EntryType,
+    depth: usize,
+    is_symlink: bool,
+    follow_link: bool,
+    record: OnceCell<Record>,
+    sample: Option<Sampled>,
+}
+
+impl Entry {
+    /// An entry for which the record has already been obtained. If `follow_link` is true, the
+    /// file type and record are those of the target when the entry is a symbolic link.
+    pub fn new(
+        path: PathBuf,
+        file_type: EntryType,
+        depth: usize,
+        is_symlink: bool,
+        follow_link: bool,
+        record: Record,
+    ) -> Entry {
+        Entry {
+            path,
+            file_type,
+            depth,
+            is_symlink,
+            follow_link,
+            record: OnceCell::from(record),
+            sample: None,
+        }
+    }
+
+    /// An entry found by walkdir, with the record of the entry if the walker already obtained it
+    pub fn from_walkdir(
+        entry: walkdir::DirEntry,
+        follow_link: bool,
+        record: Option<Record>,
+    ) -> Entry {
+        Entry {
+            file_type: entry_type(entry.file_type()),
+            depth: entry.depth(),
+            is_symlink: entry.path_is_symlink(),
+            follow_link,
+            record: record.map(OnceCell::from).unwrap_or_default(),
+            sample: None,
+            path: entry.into_path(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn file_name(&self) -> &OsStr {
+        self.path
+            .file_name()
+            .unwrap_or_else(|| self.path.as_os_str())
+    }
+
+    pub fn file_type(&self) -> EntryType {
+        self.file_type
+    }
+
+    /// Depth of the entry relative to the path the walk started from, which has depth 0
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Whether the path of the entry is a symbolic link, even if it has been followed
+    pub fn path_is_symlink(&self) -> bool {
+        self.is_symlink
+    }
+
+    /// An entry which is one of the files sampled in its directory, see [Sampled]
+    pub fn with_sample(self, sample: Option<Sampled>) -> Entry {
+        Entry { sample, ..self }
+    }
+
+    /// How the files of the directory of the entry were sampled, if the entry was sampled
+    pub fn sample(&self) -> Option<Sampled> {
+        self.sample
+    }
+
+    pub fn record(&self) -> io::Result<Record> {
+        if let Some(record) = self.record.get() {
+            return Ok(*record);
+        }
+        let record: Record = Record::stat(&self.path, self.follow_link)?;
+        Ok(*self.record.get_or_init(|| record))
+    }
+}
+
+/// The type of an entry, where a type that is not known is treated as a file. Such entries are
+/// never accepted when filtering, since the type of their record is not known either.
+pub fn entry_type(file_type: FileType) -> EntryType {
+    EntryType::of(file_type).unwrap_or(EntryType::File)
+}
+
+/// An error which occurred when walking a filesystem
+#[derive(Debug)]
+pub struct Error {
+    path: Option<PathBuf>,
+    loop_ancestor: Option<PathBuf>,
+    err: io::Error,
+}
+
+impl Error {
+    pub fn new(path: &Path, err: io::Error) -> Error {
+        Error {
+            path: Some(path.to_path_buf()),
+            loop_ancestor: None,
+            err,
+        }
+    }
+
+    /// The path which the error is associated with, if any
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// The ancestor which a symbolic link points back to, if the error is caused by a loop
+    pub fn loop_ancestor(&self) -> Option<&Path> {
+        self.loop_ancestor.as_deref()
+    }
+
+    pub fn io_error(&self) -> &io::Error {
+        &self.err
+    }
+}
+
+impl From<walkdir::Error> for Error {
+    fn from(err: walkdir::Error) -> Self {
+        let path: Option<PathBuf> = err.path().map(Path::to_path_buf);
+        let loop_ancestor: Option<PathBuf> = err.loop_ancestor().map(Path::to_path_buf);
+        let err: io::Error = match err.into_io_error() {
+            Some(err) => err,
+            None => io::Error::other("File system loop found"),
+        };
+        Error {
+            path,
+            loop_ancestor,
+            err,
+        }
+    }
+}
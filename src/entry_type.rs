@@ -0,0 +1,121 @@
+use std::fs::FileType;
+use std::str::FromStr;
+
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+
+/// The type of an entry in a filesystem
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryType {
+    File,
+    Dir,
+    Symlink,
+    Fifo,
+    Socket,
+    Block,
+    Char,
+}
+
+impl EntryType {
+    pub const ALL: [EntryType; 7] = [
+        EntryType::File,
+        EntryType::Dir,
+        EntryType::Symlink,
+        EntryType::Fifo,
+        EntryType::Socket,
+        EntryType::Block,
+        EntryType::Char,
+    ];
+
+    pub fn of(file_type: FileType) -> Option<EntryType> {
+        if file_type.is_file() {
+            Some(EntryType::File)
+        } else if file_type.is_dir() {
+            Some(EntryType::Dir)
+        } else if file_type.is_symlink() {
+            Some(EntryType::Symlink)
+        } else {
+            EntryType::special(file_type)
+        }
+    }
+
+    #[cfg(unix)]
+    fn special(file_type: FileType) -> Option<EntryType> {
+        if file_type.is_fifo() {
+            Some(EntryType::Fifo)
+        } else if file_type.is_socket() {
+            Some(EntryType::Socket)
+        } else if file_type.is_block_device() {
+            Some(EntryType::Block)
+        } else if file_type.is_char_device() {
+            Some(EntryType::Char)
+        } else {
+            None
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn special(_file_type: FileType) -> Option<EntryType> {
+        None
+    }
+
+    pub fn is_dir(&self) -> bool {
+        *self == EntryType::Dir
+    }
+
+    /// A single character which identifies the type, the same as used by `find -type`
+    pub fn indicator(&self) -> char {
+        match self {
+            EntryType::File => 'f',
+            EntryType::Dir => 'd',
+            EntryType::Symlink => 'l',
+            EntryType::Fifo => 'p',
+            EntryType::Socket => 's',
+            EntryType::Block => 'b',
+            EntryType::Char => 'c',
+        }
+    }
+}
+
+impl FromStr for EntryType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "file" | "f" => Ok(EntryType::File),
+            "dir" | "d" => Ok(EntryType::Dir),
+            "symlink" | "l" => Ok(EntryType::Symlink),
+            "fifo" | "p" => Ok(EntryType::Fifo),
+            "socket" | "s" => Ok(EntryType::Socket),
+            "block" | "b" => Ok(EntryType::Block),
+            "char" | "c" => Ok(EntryType::Char),
+            _ => Err(format!(
+                "Invalid type '{}', expected one of file, dir, symlink, fifo, socket, block or char",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EntryType;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_parse_entry_type() {
+        assert_eq!(Ok(EntryType::Fifo), EntryType::from_str("fifo"));
+        assert_eq!(Ok(EntryType::Symlink), EntryType::from_str("l"));
+    }
+
+    #[test]
+    fn test_parse_invalid_entry_type() {
+        assert!(EntryType::from_str("pipe").is_err());
+    }
+
+    #[test]
+    fn test_entry_type_of_dir() {
+        let file_type = std::fs::metadata("test_dirs").unwrap().file_type();
+        assert_eq!(Some(EntryType::Dir), EntryType::of(file_type));
+    }
+}
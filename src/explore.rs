@@ -0,0 +1,138 @@
+use crate::record::Record;
+use crate::tree::DirTree;
+use crate::usage::{DirUsage, Measure};
+use itertools::Itertools;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Directories at a depth less than this are all read before any deeper directory, so that there
+/// is something to base the estimates of the deeper directories on
+pub const SHALLOW_DEPTH: usize = 2;
+
+/// Number of directories listed in each ranking
+pub const RANKED_DIRS: usize = 10;
+
+/// Directories which are waiting to be read, where the directory that looks the largest is read
+/// first once the shallow directories have been read.
+///
+/// The estimated size of a directory is inherited from its parent, where the size of the files
+/// directly in the parent and the estimated size of the parent itself are split between its
+/// subdirectories in proportion to their number of links, which on most filesystems grows with
+/// the number of subdirectories. A directory for which the sizes of the files are known from the
+/// scan cache is estimated to be at least that large.
+pub struct BiggestFirst<T> {
+    heap: Mutex<BinaryHeap<Queued<T>>>,
+}
+
+impl<T> BiggestFirst<T> {
+    pub fn new() -> BiggestFirst<T> {
+        BiggestFirst {
+            heap: Mutex::new(BinaryHeap::new()),
+        }
+    }
+
+    pub fn push(&self, depth: usize, estimate: u64, item: T) {
+        let queued = Queued {
+            depth: Reverse(depth.min(SHALLOW_DEPTH)),
+            estimate,
+            item,
+        };
+        self.heap.lock().unwrap().push(queued);
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        self.heap.lock().unwrap().pop().map(|queued| queued.item)
+    }
+}
+
+/// An item in the queue, which is ordered only by its depth and estimated size
+struct Queued<T> {
+    depth: Reverse<usize>,
+    estimate: u64,
+    item: T,
+}
+
+impl<T> Queued<T> {
+    fn key(&self) -> (Reverse<usize>, u64) {
+        (self.depth, self.estimate)
+    }
+}
+
+impl<T> PartialEq for Queued<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl<T> Eq for Queued<T> {}
+
+impl<T> PartialOrd for Queued<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Queued<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// The weight of a subdirectory when the estimated size of its parent is split, which is the
+/// number of its subdirectories on filesystems that count the link from each of them
+pub fn weight(record: &Record) -> u64 {
+    record.nlink.saturating_sub(1).max(1)
+}
+
+/// Split `total` between a number of directories in proportion to their weights
+pub fn split(total: u64, weights: &[u64]) -> Vec<u64> {
+    let sum: u128 = weights.iter().map(|w| *w as u128).sum();
+    weights
+        .iter()
+        .map(|w| match sum {
+            0 => 0,
+            sum => (total as u128 * *w as u128 / sum) as u64,
+        })
+        .collect()
+}
+
+/// The largest directories found so far, with the size of the files found in each of them and in
+/// their subdirectories, where a complete directory has already been included in its parent
+pub fn ranking(dirs: &DirTree<DirUsage>, measure: Measure) -> Vec<(PathBuf, u64)> {
+    let files = |dir: &DirUsage| dir.files.map_or(0, |files| files.get(measure));
+    dirs.totals(files, |dir| !dir.complete)
+        .into_iter()
+        .enumerate()
+        .filter(|(_, size)| *size > 0)
+        .sorted_by_key(|(_, size)| Reverse(*size))
+        .take(RANKED_DIRS)
+        .map(|(id, size)| (dirs.path(id), size))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split, BiggestFirst};
+
+    #[test]
+    fn test_shallow_directories_first() {
+        let queue: BiggestFirst<&str> = BiggestFirst::new();
+        queue.push(3, 1000, "deep and large");
+        queue.push(1, 0, "shallow");
+        queue.push(2, 10, "deep and small");
+        queue.push(0, 0, "root");
+        let order: Vec<&str> = std::iter::from_fn(|| queue.pop()).collect();
+        assert_eq!(
+            vec!["root", "shallow", "deep and large", "deep and small"],
+            order
+        );
+    }
+
+    #[test]
+    fn test_split_by_weight() {
+        assert_eq!(vec![100, 300], split(400, &[1, 3]));
+        assert_eq!(Vec::<u64>::new(), split(400, &[]));
+    }
+}
@@ -0,0 +1,162 @@
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+/// `_IOWR('f', 11, struct fiemap)`, see linux/fs.h
+const FS_IOC_FIEMAP: u64 = 0xC020_660B;
+
+const FIEMAP_MAX_OFFSET: u64 = u64::MAX;
+const FIEMAP_EXTENT_LAST: u32 = 0x0000_0001;
+const FIEMAP_EXTENT_UNWRITTEN: u32 = 0x0000_0800;
+const FIEMAP_EXTENT_SHARED: u32 = 0x0000_2000;
+
+/// Number of extents to request from the kernel for each call to `FIEMAP`
+const EXTENTS_PER_CALL: usize = 256;
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct FiemapExtent {
+    fe_logical: u64,
+    fe_physical: u64,
+    fe_length: u64,
+    fe_reserved64: [u64; 2],
+    fe_flags: u32,
+    fe_reserved: [u32; 3],
+}
+
+#[repr(C)]
+struct Fiemap {
+    fm_start: u64,
+    fm_length: u64,
+    fm_flags: u32,
+    fm_mapped_extents: u32,
+    fm_extent_count: u32,
+    fm_reserved: u32,
+    fm_extents: [FiemapExtent; EXTENTS_PER_CALL],
+}
+
+/// How the data of a file is laid out on disk, as reported by the `FIEMAP` ioctl.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Extents {
+    /// Number of extents used to store the file
+    pub count: u64,
+    /// Bytes in extents that are shared with other files, such as snapshots or reflinked clones
+    pub shared: u64,
+    /// Bytes that are allocated but not yet written to, such as preallocated space
+    pub unwritten: u64,
+    /// Bytes within the size of the file that have no extent allocated at all
+    pub holes: u64,
+}
+
+impl Extents {
+    /// Extents per gigabyte of file size, which makes large and small files comparable when
+    /// ranking them by fragmentation
+    pub fn fragmentation(&self, size: u64) -> f64 {
+        let gigabytes: f64 = (size.max(1) as f64) / (1024.0 * 1024.0 * 1024.0);
+        (self.count as f64) / gigabytes.max(1.0)
+    }
+}
+
+/// The extents of a regular file. The file is opened without blocking, so that a FIFO which is
+/// passed by mistake can not block forever.
+pub fn extents(path: &Path) -> io::Result<Extents> {
+    let file: File = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)?;
+    let size: u64 = file.metadata()?.len();
+    let mut fiemap: Box<Fiemap> = Box::new(Fiemap {
+        fm_start: 0,
+        fm_length: FIEMAP_MAX_OFFSET,
+        fm_flags: 0,
+        fm_mapped_extents: 0,
+        fm_extent_count: EXTENTS_PER_CALL as u32,
+        fm_reserved: 0,
+        fm_extents: [FiemapExtent::default(); EXTENTS_PER_CALL],
+    });
+
+    let mut extents = Extents::default();
+    let mut end_of_last: u64 = 0;
+
+    loop {
+        let fiemap_ptr: *mut Fiemap = &mut *fiemap;
+        let result = unsafe { libc::ioctl(file.as_raw_fd(), FS_IOC_FIEMAP as _, fiemap_ptr) };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mapped: usize = fiemap.fm_mapped_extents as usize;
+        if mapped == 0 {
+            break;
+        }
+
+        for extent in &fiemap.fm_extents[..mapped] {
+            extents.count += 1;
+            if extent.fe_flags & FIEMAP_EXTENT_SHARED != 0 {
+                extents.shared += extent.fe_length;
+            }
+            if extent.fe_flags & FIEMAP_EXTENT_UNWRITTEN != 0 {
+                extents.unwritten += extent.fe_length;
+            }
+            if extent.fe_logical > end_of_last {
+                extents.holes += extent.fe_logical.min(size).saturating_sub(end_of_last);
+            }
+            end_of_last = end_of_last.max(extent.fe_logical + extent.fe_length);
+        }
+
+        let last: &FiemapExtent = &fiemap.fm_extents[mapped - 1];
+        if last.fe_flags & FIEMAP_EXTENT_LAST != 0 {
+            break;
+        }
+        fiemap.fm_start = last.fe_logical + last.fe_length;
+        fiemap.fm_length = FIEMAP_MAX_OFFSET - fiemap.fm_start;
+    }
+
+    extents.holes += size.saturating_sub(end_of_last);
+
+    Ok(extents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{extents, Extents};
+    use std::ffi::CString;
+    use std::fs;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_extents_of_small_file() {
+        let file = PathBuf::from("test_dirs/sub_dir/file2");
+        let extents: Extents = match extents(&file) {
+            Ok(extents) => extents,
+            // Not all filesystems support FIEMAP
+            Err(_) => return,
+        };
+        assert_eq!(0, extents.holes);
+        assert!(extents.count <= 1);
+    }
+
+    #[test]
+    fn test_fifo_does_not_block() {
+        let dir: PathBuf = std::env::temp_dir().join(format!("prn-fifo-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let fifo: PathBuf = dir.join("fifo");
+        let path = CString::new(fifo.as_os_str().as_bytes()).unwrap();
+        assert_eq!(0, unsafe { libc::mkfifo(path.as_ptr(), 0o600) });
+        assert!(extents(&fifo).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_fragmentation_is_per_gigabyte() {
+        let extents = Extents {
+            count: 40,
+            ..Extents::default()
+        };
+        assert_eq!(40.0, extents.fragmentation(1024));
+        assert_eq!(10.0, extents.fragmentation(4 * 1024 * 1024 * 1024));
+    }
+}
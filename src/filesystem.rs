@@ -0,0 +1,103 @@
+use std::io;
+use std::path::Path;
+
+#[cfg(unix)]
+use std::{ffi::CString, mem::MaybeUninit, os::unix::ffi::OsStrExt};
+
+/// Size and usage of a filesystem, as reported by `statvfs`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FsStats {
+    /// Size in bytes of the blocks in which the filesystem allocates space
+    pub block_size: u64,
+    /// Total number of blocks
+    pub blocks: u64,
+    /// Number of free blocks
+    pub blocks_free: u64,
+    /// Number of free blocks available to unprivileged users
+    pub blocks_available: u64,
+    /// Total number of inodes
+    pub inodes: u64,
+    /// Number of free inodes
+    pub inodes_free: u64,
+}
+
+impl FsStats {
+    pub fn size(&self) -> u64 {
+        self.blocks * self.block_size
+    }
+
+    pub fn used(&self) -> u64 {
+        (self.blocks - self.blocks_free) * self.block_size
+    }
+
+    pub fn available(&self) -> u64 {
+        self.blocks_available * self.block_size
+    }
+
+    /// Percentage of the space available to unprivileged users that is used, which is what `df`
+    /// reports
+    pub fn percent_used(&self) -> f64 {
+        let used: u64 = self.blocks - self.blocks_free;
+        let total: u64 = used + self.blocks_available;
+        match total {
+            0 => 0.0,
+            _ => 100.0 * (used as f64) / (total as f64),
+        }
+    }
+
+    pub fn percent_inodes_used(&self) -> f64 {
+        match self.inodes {
+            0 => 0.0,
+            _ => 100.0 * (self.inodes_used() as f64) / (self.inodes as f64),
+        }
+    }
+
+    pub fn inodes_used(&self) -> u64 {
+        self.inodes - self.inodes_free
+    }
+}
+
+#[cfg(unix)]
+pub fn fs_stats(path: &Path) -> io::Result<FsStats> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stats: MaybeUninit<libc::statvfs> = MaybeUninit::uninit();
+    let result = unsafe { libc::statvfs(path.as_ptr(), stats.as_mut_ptr()) };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let stats: libc::statvfs = unsafe { stats.assume_init() };
+
+    #[allow(clippy::useless_conversion)]
+    Ok(FsStats {
+        block_size: stats.f_frsize.into(),
+        blocks: stats.f_blocks.into(),
+        blocks_free: stats.f_bfree.into(),
+        blocks_available: stats.f_bavail.into(),
+        inodes: stats.f_files.into(),
+        inodes_free: stats.f_ffree.into(),
+    })
+}
+
+#[cfg(not(unix))]
+pub fn fs_stats(_path: &Path) -> io::Result<FsStats> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "Filesystem statistics are not supported on this platform",
+    ))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::{fs_stats, FsStats};
+    use std::path::Path;
+
+    #[test]
+    fn test_stats_of_current_filesystem() {
+        let stats: FsStats = fs_stats(Path::new(".")).unwrap();
+        assert!(stats.block_size > 0);
+        assert!(stats.used() <= stats.size());
+        assert!(stats.available() <= stats.size());
+        assert!((0.0..=100.0).contains(&stats.percent_used()));
+        assert!(stats.inodes_used() <= stats.inodes);
+    }
+}
@@ -1,25 +1,28 @@
 use regex::Regex;
 use std::{
-    fs::Metadata,
     ops::RangeInclusive,
+    path::Path,
     time::{Duration, SystemTime},
 };
-use walkdir::DirEntry;
 
 use crate::{
     cfg::{Config, Mode},
+    entry::Entry,
+    entry_type::EntryType,
+    record::Record,
     size::Size,
+    usage::{Measure, Usage},
 };
 
 pub struct Filter {
     mod_age: Option<RangeInclusive<Duration>>,
     pattern: Option<Regex>,
+    types: Vec<EntryType>,
     min_size: u64,
+    measure: Measure,
     mode: Mode,
 }
 
-const PROC: &str = "/proc";
-
 impl Filter {
     #[cfg(test)]
     pub fn new() -> Filter {
@@ -32,60 +35,76 @@ impl Filter {
         self
     }
 
+    #[cfg(test)]
+    pub fn with_types(mut self, types: &[EntryType]) -> Self {
+        self.types = types.to_vec();
+        self
+    }
+
     #[cfg(test)]
     pub fn with_min_size(mut self, min_size: Size) -> Self {
         self.min_size = min_size.as_bytes();
         self
     }
 
-    pub fn accept(&self, e: &DirEntry) -> bool {
-        let metadata: Metadata = match e.metadata() {
-            Ok(metadata) => metadata,
+    pub fn accept(&self, e: &Entry) -> bool {
+        let record: Record = match e.record() {
+            Ok(record) => record,
             Err(err) => {
                 log::warn!("Unable to obtain metadata for {:?}: {:?}", e.path(), err);
                 return false;
             }
         };
 
-        if let Mode::File = self.mode {
-            if metadata.len() < self.min_size {
+        self.accept_file(e.path(), &record)
+    }
+
+    /// Whether every entry is accepted regardless of its name, type and age, so that entries do not
+    /// have to be stat'ed to be filtered when the size does not matter either
+    pub fn accepts_any_entry(&self) -> bool {
+        self.pattern.is_none()
+            && self.mod_age.is_none()
+            && EntryType::ALL.iter().all(|t| self.types.contains(t))
+    }
+
+    /// Apply the filter to a file for which the record has already been obtained
+    pub fn accept_file(&self, path: &Path, record: &Record) -> bool {
+        if let Mode::File | Mode::Extents | Mode::DeletedOpen = self.mode {
+            if Usage::of(record).get(self.measure) < self.min_size {
                 return false;
             }
         }
 
-        if !metadata.is_file() {
-            return false;
+        match record.entry_type {
+            Some(entry_type) if self.types.contains(&entry_type) => (),
+            _ => return false,
         }
 
-        let accept_age: bool = Filter::filter_mod_time(&metadata, &self.mod_age);
+        let accept_age: bool = Filter::filter_mod_time(record, &self.mod_age);
 
         if !accept_age {
             return false;
         }
 
-        let file_name: String = match Filter::file_name(e) {
+        let file_name: String = match Filter::file_name(path) {
             Some(name) => name,
             None => return false,
         };
 
-        if e.path().starts_with(PROC) {
-            return false;
-        }
-
         match &self.pattern {
             Some(pattern) => pattern.is_match(&file_name),
             None => true,
         }
     }
 
-    fn file_name(entry: &DirEntry) -> Option<String> {
-        match entry.path().file_name() {
+    fn file_name(path: &Path) -> Option<String> {
+        match path.file_name() {
             Some(name) => name.to_str().map(|n| n.to_string()),
             None => None,
         }
     }
 
-    fn filter_mod_time(metadata: &Metadata, range: &Option<RangeInclusive<Duration>>) -> bool {
+    fn filter_mod_time(record: &Record, range: &Option<RangeInclusive<Duration>>) -> bool {
         let range: &RangeInclusive<Duration> = match range {
             Some(age_range) => age_range,
             None => return true,
@@ -95,9 +114,9 @@ impl Filter {
             return false;
         }
 
-        let mod_time: SystemTime = match metadata.modified() {
-            Ok(m) => m,
-            Err(_) => return false,
+        let mod_time: SystemTime = match record.mtime {
+            Some(m) => m,
+            None => return false,
         };
 
         let now = SystemTime::now();
@@ -124,7 +143,13 @@ impl From<&Config> for Filter {
         Filter {
             mod_age: mod_age_range(&cfg.min_age, &cfg.max_age),
             pattern: cfg.pattern.clone(),
+            types: match (cfg.types.is_empty(), cfg.mode()) {
+                (true, Mode::Count) => EntryType::ALL.to_vec(),
+                (true, _) => vec![EntryType::File],
+                (false, _) => cfg.types.clone(),
+            },
             min_size: cfg.min_size_bytes(),
+            measure: cfg.measure(),
             mode: cfg.mode(),
         }
     }
@@ -147,18 +172,20 @@ impl Default for Filter {
         Filter {
             mod_age: None,
             pattern: None,
+            types: vec![EntryType::File],
             min_size: Size::Megabyte(100).as_bytes(),
+            measure: Measure::Apparent,
             mode: Mode::File,
         }
     }
 }
 
-pub fn summarize(files: Vec<DirEntry>) -> (u64, u64) {
+pub fn summarize(files: Vec<Entry>, measure: Measure) -> (u64, u64) {
     let found: u64 = files.len() as u64;
     let size: u64 = files
         .iter()
-        .filter_map(|f| f.metadata().ok())
-        .map(|m| m.len())
+        .filter_map(|f| f.record().ok())
+        .map(|r| Usage::of(&r).get(measure))
         .sum();
 
     (found, size)
@@ -166,13 +193,15 @@ pub fn summarize(files: Vec<DirEntry>) -> (u64, u64) {
 
 #[cfg(test)]
 mod tests {
+    use crate::entry::Entry;
+    use crate::entry_type::EntryType;
     use crate::find::Filter;
     use crate::size::Size;
+    use crate::usage::Measure;
     use crate::{cfg::Config, create_walker, find::summarize, walk_files};
     use regex::Regex;
     use std::path::PathBuf;
     use std::str::FromStr;
-    use walkdir::DirEntry;
 
     const TEST_DIR: &str = "test_dirs";
     const PROC: &str = "/proc";
@@ -180,12 +209,12 @@ mod tests {
     #[test]
     fn test_stop_at_one_found_file() {
         let dir = PathBuf::from(TEST_DIR);
-        let files: Vec<DirEntry> = create_walker(&Config::default(), &dir)
+        let files: Vec<Entry> = create_walker(&Config::default(), &dir)
             .into_iter()
             .filter_map(|e| e.ok())
             .take(1)
             .collect();
-        let result: (u64, u64) = summarize(files);
+        let result: (u64, u64) = summarize(files, Measure::Apparent);
         assert_eq!(1, result.0);
     }
 
@@ -194,17 +223,33 @@ mod tests {
     fn test_filter_by_file_size() {
         let dir = PathBuf::from(TEST_DIR);
         let filter = Filter::new().with_min_size(Size::Byte(100));
-        let files: Vec<DirEntry> = create_walker(&Config::default(), &dir)
+        let files: Vec<Entry> = create_walker(&Config::default(), &dir)
             .into_iter()
             .filter_map(|e| e.ok())
-            .filter(|e| filter.accept(&e))
+            .filter(|e| filter.accept(e))
             .collect();
 
-        let result: (u64, u64) = summarize(files);
+        let result: (u64, u64) = summarize(files, Measure::Apparent);
         assert_eq!(1, result.0);
         assert_eq!(100, result.1);
     }
 
+    #[test]
+    fn test_filter_by_entry_type() {
+        let dir = PathBuf::from(TEST_DIR);
+        let filter = Filter::new()
+            .with_types(&[EntryType::Dir])
+            .with_min_size(Size::Byte(0));
+        let dirs: Vec<Entry> = create_walker(&Config::default(), &dir)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| filter.accept(e))
+            .collect();
+
+        assert_eq!(2, dirs.len());
+        assert!(dirs.iter().all(|e| e.file_type().is_dir()));
+    }
+
     #[cfg(target_os = "linux")]
     #[test]
     fn test_filter_out_proc() {
@@ -220,10 +265,10 @@ mod tests {
         let filter = Filter::new()
             .with_pattern(pattern)
             .with_min_size(Size::Byte(1));
-        let files: Vec<DirEntry> = create_walker(&Config::default(), &dir)
+        let files: Vec<Entry> = create_walker(&Config::default(), &dir)
             .into_iter()
             .filter_map(|e| e.ok())
-            .filter(|f| filter.accept(&f))
+            .filter(|f| filter.accept(f))
             .collect();
 
         assert_eq!(2, files.len());
@@ -0,0 +1,78 @@
+use crate::progress;
+#[cfg(not(unix))]
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+
+/// Number of SIGINT, which is assumed when the signal which was received is not known
+const SIGINT: i32 = 2;
+
+/// Signals which stop the search
+#[cfg(unix)]
+const SIGNALS: [libc::c_int; 3] = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP];
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Set once it has been told that the search was interrupted
+static NOTIFIED: AtomicBool = AtomicBool::new(false);
+
+/// The last signal which was received
+static SIGNAL: AtomicI32 = AtomicI32::new(SIGINT);
+
+/// Stop the search when the process receives SIGINT, SIGTERM or SIGHUP, so that the results found
+/// so far can still be printed. The process exits right away when it receives a second signal,
+/// such as when printing the results takes too long.
+#[cfg(unix)]
+pub fn stop_on_interrupt() {
+    for signal in SIGNALS {
+        let installed: bool = unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_signal as extern "C" fn(libc::c_int) as usize;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(signal, &action, std::ptr::null_mut()) == 0
+        };
+        if !installed {
+            let err = std::io::Error::last_os_error();
+            log::warn!("Unable to handle signal {}: {}", signal, err);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn stop_on_interrupt() {
+    let result = ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::Relaxed) {
+            process::exit(exit_code());
+        }
+    });
+    if let Err(err) = result {
+        log::warn!("Unable to handle interrupts: {}", err);
+    }
+}
+
+/// Only does what is safe to do in a signal handler, which is to record the signal, or to exit
+/// right away if a signal has already been received
+#[cfg(unix)]
+extern "C" fn on_signal(signal: libc::c_int) {
+    SIGNAL.store(signal, Ordering::Relaxed);
+    if INTERRUPTED.swap(true, Ordering::Relaxed) {
+        unsafe { libc::_exit(128 + signal) };
+    }
+}
+
+/// Whether the search has been interrupted, and should stop. The first time it is found to have
+/// been interrupted, this is told on stderr, which can not be done in the signal handler.
+pub fn interrupted() -> bool {
+    let interrupted: bool = INTERRUPTED.load(Ordering::Relaxed);
+    if interrupted && !NOTIFIED.swap(true, Ordering::Relaxed) {
+        progress::clear();
+        eprintln!("Interrupted, printing the results found so far. Interrupt again to exit now.");
+    }
+    interrupted
+}
+
+/// Exit code when interrupted, which by convention is 128 plus the number of the signal, such as
+/// 130 for SIGINT and 143 for SIGTERM
+pub fn exit_code() -> i32 {
+    128 + SIGNAL.load(Ordering::Relaxed)
+}
@@ -0,0 +1,77 @@
+use crate::walk::WalkResult;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// A symbolic link which points to a file that does not exist
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokenLink {
+    pub path: PathBuf,
+    pub target: PathBuf,
+}
+
+/// Returns the broken link for an entry in a walk, if the entry is a symbolic link whose target
+/// does not exist. When symbolic links are followed, a broken link is reported as an error by the
+/// walker rather than as an entry, so both cases are handled.
+pub fn broken_link(entry: &WalkResult) -> Option<BrokenLink> {
+    let path: &Path = match entry {
+        Ok(entry) if entry.path_is_symlink() => entry.path(),
+        Ok(_) => return None,
+        Err(err) if err.io_error().kind() == ErrorKind::NotFound => err.path()?,
+        Err(_) => return None,
+    };
+
+    if !fs::symlink_metadata(path).ok()?.file_type().is_symlink() {
+        return None;
+    }
+
+    match fs::metadata(path) {
+        Err(err) if err.kind() == ErrorKind::NotFound => Some(BrokenLink {
+            path: path.to_path_buf(),
+            target: fs::read_link(path).ok()?,
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::{broken_link, BrokenLink};
+    use crate::cfg::Config;
+    use crate::walk::create_walker;
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use std::path::{Path, PathBuf};
+
+    fn broken_links(dir: &Path, follow_links: bool, threads: usize) -> Vec<BrokenLink> {
+        let cfg = Config::default()
+            .with_follow_links(follow_links)
+            .with_threads(threads);
+        create_walker(&cfg, dir)
+            .into_iter()
+            .filter_map(|e| broken_link(&e))
+            .collect()
+    }
+
+    #[test]
+    fn test_find_broken_link() {
+        let dir = std::env::temp_dir().join(format!("prn-broken-links-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        symlink("missing", dir.join("broken")).unwrap();
+        fs::write(dir.join("target"), "content").unwrap();
+        symlink("target", dir.join("valid")).unwrap();
+
+        let expected = vec![BrokenLink {
+            path: dir.join("broken"),
+            target: PathBuf::from("missing"),
+        }];
+        let not_followed: Vec<BrokenLink> = broken_links(&dir, false, 1);
+        let followed: Vec<BrokenLink> = broken_links(&dir, true, 1);
+        let parallel: Vec<BrokenLink> = broken_links(&dir, true, 4);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(expected, not_followed);
+        assert_eq!(expected, followed);
+        assert_eq!(expected, parallel);
+    }
+}
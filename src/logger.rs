@@ -1,3 +1,4 @@
+use crate::progress;
 use env_logger::fmt::{Color, Formatter};
 use log::{Level, LevelFilter, Record};
 use std::io;
@@ -34,6 +35,7 @@ fn log_by_cmd_arg(verbosity_level: u8) {
 }
 
 fn formatter(buf: &mut Formatter, record: &Record) -> io::Result<()> {
+    progress::clear();
     match record.level() {
         Level::Info => writeln!(buf, "{}", record.args()),
         Level::Warn => {
@@ -2,28 +2,92 @@ extern crate humansize;
 extern crate lazy_static;
 extern crate structopt;
 
+mod bloat;
+mod budget;
+mod cache;
 mod cfg;
 mod dbg;
 mod duration;
+mod entry;
+mod entry_type;
+mod explore;
+#[cfg(target_os = "linux")]
+mod extent;
+mod filesystem;
 mod find;
+mod interrupt;
+mod links;
 mod logger;
+mod mounts;
+mod parallel;
 mod parse;
 mod print;
+mod proc;
+mod progress;
+mod record;
+mod remote;
+mod sample;
 mod size;
+mod slack;
+mod stats;
+mod throttle;
+mod tree;
+mod usage;
+mod walk;
 
+use crate::bloat::DirSize;
+use crate::budget::INCOMPLETE_EXIT_CODE;
+use crate::cache::{collect_garbage, ScanCache};
 use crate::cfg::Config;
 use crate::dbg::dbg_info;
+#[cfg(target_os = "linux")]
+use crate::entry_type::EntryType;
+use crate::explore::ranking;
+#[cfg(target_os = "linux")]
+use crate::extent::{extents, Extents};
+use crate::filesystem::fs_stats;
 use crate::find::summarize;
+use crate::interrupt::{interrupted, stop_on_interrupt};
+use crate::links::{broken_link, BrokenLink};
 use crate::logger::setup_logging;
-use crate::print::{print_dir, print_file, print_summary};
+use crate::print::{
+    print_bloated, print_broken_link, print_collected, print_count, print_dir, print_distribution,
+    print_file, print_mount, print_ranking, print_slack, print_stats, print_summary,
+};
+#[cfg(target_os = "linux")]
+use crate::print::{print_deleted, print_extents};
+#[cfg(target_os = "linux")]
+use crate::proc::{deleted_open_files, DeletedFile};
+use crate::proc::{OpenFiles, Opener};
+use crate::progress::Progress;
+use crate::record::Record;
+use crate::sample::margin;
+use crate::slack::{Distribution, Slack, DEFAULT_BLOCK_SIZE};
+use crate::stats::Stats;
 use crate::structopt::StructOpt;
-use cfg::Mode;
+use crate::tree::DirTree;
+use cfg::{CacheCommand, Command, Mode, Sort};
+use entry::Entry;
 use find::Filter;
 use itertools::Itertools;
+use mounts::FsTypeFilter;
+#[cfg(target_os = "linux")]
+use mounts::{mount_of, mount_of_dev};
+#[cfg(target_os = "linux")]
+use std::cmp::Ordering;
+use std::cmp::Reverse;
 use std::collections::HashMap;
+#[cfg(target_os = "linux")]
+use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
 use std::process;
-use walkdir::{DirEntry, WalkDir};
+use std::sync::Arc;
+use std::time::Instant;
+use usage::{DirUsage, Usage};
+use walk::{walk, walk_cached, was_stopped};
+
+#[cfg(test)]
+use walk::create_walker;
 
 fn main() {
     let cfg = Config::from_args();
@@ -35,90 +99,530 @@ fn main() {
         process::exit(0);
     }
 
+    if let Some(Command::Mounts) = cfg.command {
+        list_mounts(&cfg);
+        process::exit(0);
+    }
+
+    if let Some(Command::Cache(CacheCommand::Gc)) = cfg.command {
+        match collect_garbage() {
+            Ok(collected) => print_collected(&collected, &cfg),
+            Err(err) => {
+                log::error!("Unable to remove stale directories from the cache: {}", err);
+                process::exit(1);
+            }
+        }
+        process::exit(0);
+    }
+
+    if let Some(threshold) = cfg.fuller_than {
+        log::info!(
+            "Searching filesystems more than {}% full: {:?}",
+            threshold,
+            cfg.paths()
+        );
+    }
+
+    throttle::configure(&cfg);
+    stop_on_interrupt();
+    budget::start(&cfg);
+    let start = Instant::now();
+    let progress: Option<Progress> = Progress::start(&cfg);
     let (found, size) = match cfg.mode() {
         Mode::File => walk_files(&cfg),
         Mode::Dir => walk_dirs(&cfg),
+        Mode::Extents => walk_extents(&cfg),
+        Mode::DeletedOpen => find_deleted_open(&cfg),
+        Mode::Count => walk_count(&cfg),
+        Mode::Slack => walk_slack(&cfg),
+        Mode::Bloated => walk_bloated(&cfg),
+        Mode::BrokenLinks => find_broken_links(&cfg),
     };
 
+    drop(progress);
     print_summary(cfg.mode(), found, size, &cfg);
+    if cfg.stats {
+        print_stats(&Stats::get(), start.elapsed(), &cfg);
+    }
+    if interrupted() {
+        process::exit(interrupt::exit_code());
+    }
+    if was_stopped() {
+        process::exit(INCOMPLETE_EXIT_CODE);
+    }
+}
+
+fn list_mounts(cfg: &Config) {
+    let fs_types: FsTypeFilter = cfg.into();
+    for mount in fs_types.real_mounts() {
+        match fs_stats(&mount.mount_point) {
+            Ok(stats) => print_mount(mount, &stats, cfg),
+            Err(err) => log::warn!("Unable to obtain usage of {:?}: {}", mount.mount_point, err),
+        }
+    }
 }
 
 fn walk_files(cfg: &Config) -> (u64, u64) {
     let limit: usize = cfg.limit.unwrap_or(usize::MAX);
     let filter: Filter = cfg.into();
-    let files: Vec<DirEntry> = cfg
+    let open_files: Option<OpenFiles> = match cfg.open_by() {
+        true => Some(OpenFiles::scan()),
+        false => None,
+    };
+    let files = walk(cfg)
+        .filter_map(|e| e.ok())
+        .filter(|e: &Entry| filter.accept(e))
+        .filter(|e: &Entry| accept_open(openers_of(e, &open_files), cfg));
+
+    let files: Vec<Entry> = sort_files(files, cfg)
+        .take(limit)
+        .inspect(|f| print_file(f, openers_of(f, &open_files), cfg))
+        .collect();
+
+    summarize(files, cfg.measure())
+}
+
+/// Sort files if requested, which requires that all files have been found first
+fn sort_files<'a, I>(files: I, cfg: &Config) -> Box<dyn Iterator<Item = Entry> + 'a>
+where
+    I: Iterator<Item = Entry> + 'a,
+{
+    let measure = cfg.measure();
+    let size = |e: &Entry| e.record().map(|r| Usage::of(&r).get(measure)).unwrap_or(0);
+    match cfg.sort {
+        Some(Sort::Size) => {
+            Box::new(files.sorted_by_cached_key(|e| (Reverse(size(e)), e.path().to_path_buf())))
+        }
+        Some(Sort::Path) => Box::new(files.sorted_by(|e0, e1| e0.path().cmp(e1.path()))),
+        None => Box::new(files),
+    }
+}
+
+fn openers_of<'a>(entry: &Entry, open_files: &'a Option<OpenFiles>) -> Option<&'a [Opener]> {
+    let open_files: &OpenFiles = open_files.as_ref()?;
+    let record = entry.record().ok()?;
+    Some(open_files.openers(&record))
+}
+
+fn accept_open(openers: Option<&[Opener]>, cfg: &Config) -> bool {
+    match openers {
+        Some(openers) if cfg.only_open => !openers.is_empty(),
+        Some(openers) if cfg.only_closed => openers.is_empty(),
+        _ => true,
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn walk_extents(cfg: &Config) -> (u64, u64) {
+    let limit: usize = cfg.limit.unwrap_or(usize::MAX);
+    let filter: Filter = cfg.into();
+    let measure = cfg.measure();
+    let files: Vec<u64> = walk(cfg)
+        .filter_map(|e| e.ok())
+        .filter(|e: &Entry| filter.accept(e))
+        .filter_map(|e: Entry| extents_of(&e).map(|(usage, extents)| (e, usage, extents)))
+        .sorted_by(|(file0, usage0, extents0), (file1, usage1, extents1)| {
+            let frag0: f64 = extents0.fragmentation(usage0.apparent);
+            let frag1: f64 = extents1.fragmentation(usage1.apparent);
+            frag1
+                .partial_cmp(&frag0)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| file0.path().cmp(file1.path()))
+        })
+        .take(limit)
+        .inspect(|(file, usage, extents)| print_extents(file.path(), *usage, extents, cfg))
+        .map(|(_, usage, _)| usage.get(measure))
+        .collect();
+
+    (files.len() as u64, files.iter().sum())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn walk_extents(_cfg: &Config) -> (u64, u64) {
+    log::error!("Extent analysis is only supported on Linux");
+    process::exit(1);
+}
+
+#[cfg(target_os = "linux")]
+fn extents_of(entry: &Entry) -> Option<(Usage, Extents)> {
+    let record: Record = entry.record().ok()?;
+    // Only regular files have extents, and opening a FIFO would block
+    if record.entry_type != Some(EntryType::File) {
+        return None;
+    }
+    let usage: Usage = Usage::of(&record);
+    match extents(entry.path()) {
+        Ok(extents) => Some((usage, extents)),
+        Err(err) => {
+            log::warn!("Unable to obtain extents for {:?}: {}", entry.path(), err);
+            None
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn find_deleted_open(cfg: &Config) -> (u64, u64) {
+    let limit: usize = cfg.limit.unwrap_or(usize::MAX);
+    let filter: Filter = cfg.into();
+    let measure = cfg.measure();
+    let roots: Vec<(PathBuf, u64)> = cfg
         .paths()
         .iter()
-        .map(|path: &PathBuf| create_walker(cfg, path))
+        .filter_map(|path: &PathBuf| {
+            let path: PathBuf = path.canonicalize().ok()?;
+            let dev: u64 = path.metadata().ok()?.dev();
+            Some((path, dev))
+        })
+        .collect();
+
+    // Files in memory, such as in /dev/shm or those created by memfd_create, do not occupy any
+    // space on disk, and neither do files on filesystems which are not mounted
+    let fs_types: FsTypeFilter = cfg.into();
+    let on_disk = |f: &DeletedFile| mount_of_dev(f.record.dev).is_some_and(|m| fs_types.on_disk(m));
+
+    let files: Vec<DeletedFile> = deleted_open_files()
         .into_iter()
-        .flatten()
-        .filter_map(|e| e.ok())
-        .filter(|e: &DirEntry| filter.accept(e))
+        .filter(|f: &DeletedFile| filter.accept_file(&f.path, &f.record))
+        .filter(|f: &DeletedFile| {
+            roots.iter().any(|(root, dev)| {
+                f.path.starts_with(root) && (!cfg.only_local_fs() || f.record.dev == *dev)
+            })
+        })
+        .filter(on_disk)
+        .sorted_by_key(|f: &DeletedFile| Reverse(Usage::of(&f.record).get(measure)))
         .take(limit)
-        .inspect(|f| print_file(f, cfg))
+        .inspect(|f: &DeletedFile| print_deleted(f, cfg))
         .collect();
 
-    summarize(files)
+    // Each file is listed once for every process which holds it open, but the space it occupies
+    // is only counted once
+    let size: u64 = files
+        .iter()
+        .unique_by(|f| f.inode())
+        .map(|f| Usage::of(&f.record).get(measure))
+        .sum();
+
+    (files.len() as u64, size)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn find_deleted_open(_cfg: &Config) -> (u64, u64) {
+    log::error!("Finding deleted files that are still open is only supported on Linux");
+    process::exit(1);
+}
+
+fn find_broken_links(cfg: &Config) -> (u64, u64) {
+    let limit: usize = cfg.limit.unwrap_or(usize::MAX);
+    let links = walk(cfg).filter_map(|e| broken_link(&e));
+    let links: Box<dyn Iterator<Item = BrokenLink>> = match cfg.sort {
+        Some(_) => Box::new(links.sorted_by(|link0, link1| link0.path.cmp(&link1.path))),
+        None => Box::new(links),
+    };
+    let links: Vec<BrokenLink> = links
+        .take(limit)
+        .inspect(|link: &BrokenLink| print_broken_link(link, cfg))
+        .collect();
+
+    (links.len() as u64, 0)
 }
 
 fn walk_dirs(cfg: &Config) -> (u64, u64) {
-    let mut acc_size: HashMap<PathBuf, u64> = HashMap::new();
+    let cache: Option<Arc<ScanCache>> = match cfg.use_cache() {
+        true => ScanCache::open().map(Arc::new),
+        false => None,
+    };
+    walk_dirs_cached(cfg, cache)
+}
+
+/// Search for directories, where the entries of directories which have not been modified are read
+/// from the cache, if one is given
+fn walk_dirs_cached(cfg: &Config, cache: Option<Arc<ScanCache>>) -> (u64, u64) {
     let paths: Vec<PathBuf> = cfg.paths();
+    let mut dirs: DirTree<DirUsage> = DirTree::new(&paths);
+    let filter: Filter = cfg.into();
+    let measure = cfg.measure();
+
+    let mut listing = DirListing::new(cfg);
+    let start = Instant::now();
+    let mut ranked = Instant::now();
+    // The entry of each directory comes after all the entries below it, so the usage of a
+    // directory is complete when its entry is found, and it can be printed right away
+    walk_cached(cfg, cache.clone())
+        .filter_map(|e| e.ok())
+        .for_each(|e: Entry| {
+            if cfg.biggest_first && ranked.elapsed() >= cfg.ranking_interval {
+                print_ranking(&ranking(&dirs, measure), start.elapsed(), cfg);
+                ranked = Instant::now();
+            }
+            let usage: Option<Usage> = filter.accept(&e).then(|| size_of(&e));
+            let completed: Option<DirUsage> = match e.file_type().is_dir() {
+                true => dirs.get_mut(e.path()).map(|dir| {
+                    if let Ok(record) = e.record() {
+                        dir.own = Usage::of(&record).get(measure);
+                    }
+                    dir.complete = true;
+                    *dir
+                }),
+                false => None,
+            };
+            if let Some(dir) = completed {
+                let files: Option<Usage> = dir.files;
+                let files = files.filter(|usage| usage.get(measure) >= cfg.min_size_bytes());
+                if let Some(files) = files {
+                    let margin: Option<u64> = cfg.sample.map(|_| margin(dir.variance()));
+                    listing.add(e.path(), files, dir.own, margin);
+                }
+            }
+            if let Some(parent) = e.path().parent().and_then(|p| dirs.get_mut(p)) {
+                if let Some(dir) = &completed {
+                    parent.add_subdir(dir);
+                }
+                match (e.sample(), usage) {
+                    (Some(sample), _) => parent.add_sampled(usage, &sample, measure),
+                    (None, Some(usage)) => parent.add_file(usage),
+                    (None, None) => (),
+                }
+            }
+        });
+    if let Some(cache) = cache {
+        if let Err(err) = cache.save() {
+            log::warn!("Unable to save the cache: {}", err);
+        }
+    }
+
+    // When stopped, the directories which were not completely walked are listed with the usage of
+    // the files found in them so far. Their own entries were not found, so their own size is
+    // obtained here.
+    if was_stopped() {
+        dirs.accumulate(|parent, dir| {
+            if !dir.complete {
+                parent.add_subdir(dir)
+            }
+        });
+        dirs.iter()
+            .filter(|(_, dir)| !dir.complete)
+            .filter_map(|(id, dir)| Some((id, dir.files?, dir)))
+            .filter(|(_, files, _)| files.get(measure) >= cfg.min_size_bytes())
+            .map(|(id, files, dir)| (dirs.path(id), files, dir))
+            .sorted_by(|(path0, _, _), (path1, _, _)| path0.cmp(path1))
+            .take(listing.remaining())
+            .for_each(|(path, files, dir)| {
+                let own: u64 = match Record::stat(&path, true) {
+                    Ok(record) => Usage::of(&record).get(measure),
+                    Err(_) => dir.own,
+                };
+                let margin: Option<u64> = cfg.sample.map(|_| margin(dir.variance()));
+                listing.add(&path, files, own, margin);
+            });
+    }
+
+    listing.finish()
+}
+
+/// The directories listed in dir mode, which are printed as soon as they are found, unless they
+/// are to be sorted. Sorting requires that all directories have been found first, and the limit
+/// then applies to the sorted directories.
+struct DirListing<'a> {
+    cfg: &'a Config,
+    limit: u64,
+    /// Path, usage of the files, own size and margin of each directory to sort
+    sorted: Vec<(PathBuf, Usage, u64, Option<u64>)>,
+    found: u64,
+    size: u64,
+}
+
+impl<'a> DirListing<'a> {
+    fn new(cfg: &'a Config) -> DirListing<'a> {
+        DirListing {
+            cfg,
+            limit: cfg.limit.unwrap_or(usize::MAX) as u64,
+            sorted: Vec::new(),
+            found: 0,
+            size: 0,
+        }
+    }
+
+    fn add(&mut self, path: &Path, files: Usage, own: u64, margin: Option<u64>) {
+        match self.cfg.sort {
+            Some(_) => self.sorted.push((path.to_path_buf(), files, own, margin)),
+            None => self.print(path, files, own, margin),
+        }
+    }
+
+    /// How many more directories that may be listed
+    fn remaining(&self) -> usize {
+        match self.cfg.sort {
+            Some(_) => usize::MAX,
+            None => self.limit.saturating_sub(self.found) as usize,
+        }
+    }
+
+    fn print(&mut self, path: &Path, files: Usage, own: u64, margin: Option<u64>) {
+        if self.found < self.limit {
+            print_dir(path, files, own, margin, self.cfg);
+            self.found += 1;
+            self.size = self.size.max(files.get(self.cfg.measure()));
+        }
+    }
+
+    fn finish(mut self) -> (u64, u64) {
+        let measure = self.cfg.measure();
+        let mut sorted = std::mem::take(&mut self.sorted);
+        match self.cfg.sort {
+            Some(Sort::Size) => sorted.sort_by_cached_key(|(path, files, _, _)| {
+                (Reverse(files.get(measure)), path.clone())
+            }),
+            Some(Sort::Path) => sorted.sort_by(|(path0, ..), (path1, ..)| path0.cmp(path1)),
+            None => (),
+        }
+        for (path, files, own, margin) in sorted {
+            self.print(&path, files, own, margin);
+        }
+        (self.found, self.size)
+    }
+}
+
+fn walk_slack(cfg: &Config) -> (u64, u64) {
+    let paths: Vec<PathBuf> = cfg.paths();
+    let mut dirs: DirTree<Slack> = DirTree::new(&paths);
     let root: &Path = paths.first().unwrap();
     let filter: Filter = cfg.into();
+    let block_size: u64 = block_size(root);
+    let mut distribution = Distribution::new(block_size);
 
-    cfg.paths()
-        .iter()
-        .map(|path: &PathBuf| create_walker(cfg, path))
-        .flatten()
+    walk(cfg)
         .filter_map(|e| e.ok())
-        .filter(|e: &DirEntry| filter.accept(e))
-        .map(|f: DirEntry| size_of(&f))
-        .for_each(|(dir, size)| update_size(&mut acc_size, dir, root, size));
+        .filter(|e: &Entry| filter.accept(e))
+        .for_each(|f: Entry| {
+            let size: Usage = size_of(&f);
+            distribution.add(size);
+            if let Some(dir) = f.path().parent().and_then(|p| dirs.get_mut(p)) {
+                *dir += Slack::of(size);
+            }
+        });
+    dirs.accumulate(|dir, subdir| *dir += *subdir);
 
     let limit: usize = cfg.limit.unwrap_or(usize::MAX);
-    let acc_size: Vec<u64> = acc_size
+    let slack: Vec<u64> = dirs
         .iter()
-        .filter(|(_, size)| **size >= cfg.min_size_bytes())
+        .filter(|(_, slack)| slack.slack >= cfg.min_slack_bytes())
+        .map(|(id, slack)| (dirs.path(id), *slack))
+        .sorted_by(|(path0, slack0), (path1, slack1)| {
+            slack1
+                .slack
+                .cmp(&slack0.slack)
+                .then_with(|| path0.cmp(path1))
+        })
         .take(limit)
-        .sorted_by(|(path0, _), (path1, _)| path0.cmp(path1))
-        .inspect(|(path, size)| print_dir(path, **size, cfg))
-        .map(|(_, size)| *size)
+        .inspect(|(path, slack)| print_slack(path, *slack, cfg))
+        .map(|(_, slack)| slack.slack)
         .collect_vec();
 
-    let size: u64 = *acc_size.iter().max().unwrap_or(&0);
-    let found: u64 = acc_size.len() as u64;
+    print_distribution(&distribution, cfg);
+
+    let size: u64 = *slack.iter().max().unwrap_or(&0);
+    let found: u64 = slack.len() as u64;
 
     (found, size)
 }
 
-fn update_size(acc_size: &mut HashMap<PathBuf, u64>, path: PathBuf, root: &Path, size: u64) {
-    let cur_size: u64 = *acc_size.get(&path).unwrap_or(&0u64);
-    let new_size = cur_size + size;
-    acc_size.insert(path.clone(), new_size);
-    if path == *root {
-        return;
-    }
-    if let Some(parent) = path.parent() {
-        update_size(acc_size, PathBuf::from(parent), root, size)
-    }
+fn walk_count(cfg: &Config) -> (u64, u64) {
+    let paths: Vec<PathBuf> = cfg.paths();
+    // The number of entries directly in each directory, and in the directory and all of its
+    // subdirectories
+    let mut dirs: DirTree<(u64, u64)> = DirTree::new(&paths);
+    let filter: Filter = cfg.into();
+    let any_entry: bool = filter.accepts_any_entry();
+
+    walk(cfg)
+        .filter_map(|e| e.ok())
+        .filter(|e: &Entry| e.depth() > 0)
+        .filter(|e: &Entry| any_entry || filter.accept(e))
+        .for_each(|e: Entry| {
+            if let Some((direct, recursive)) = e.path().parent().and_then(|p| dirs.get_mut(p)) {
+                *direct += 1;
+                *recursive += 1;
+            }
+        });
+    dirs.accumulate(|(_, recursive), (_, subdir)| *recursive += subdir);
+
+    let limit: usize = cfg.limit.unwrap_or(usize::MAX);
+    let counts: Vec<u64> = dirs
+        .iter()
+        .filter(|(_, (_, count))| *count > 0 && *count >= cfg.min_entries)
+        .map(|(id, (direct, count))| (dirs.path(id), *direct, *count))
+        .sorted_by(|(path0, _, count0), (path1, _, count1)| {
+            count1.cmp(count0).then_with(|| path0.cmp(path1))
+        })
+        .take(limit)
+        .inspect(|(path, direct, count)| print_count(path, *direct, *count, cfg))
+        .map(|(_, _, count)| count)
+        .collect_vec();
+
+    let entries: u64 = *counts.iter().max().unwrap_or(&0);
+    let found: u64 = counts.len() as u64;
+
+    (found, entries)
 }
 
-fn size_of(entry: &DirEntry) -> (PathBuf, u64) {
-    let size: u64 = match entry.metadata() {
-        Ok(metadata) => metadata.len(),
-        Err(_) => 0,
-    };
-    let parent: PathBuf = entry.path().parent().unwrap().to_path_buf();
-    (parent, size)
+fn walk_bloated(cfg: &Config) -> (u64, u64) {
+    let paths: Vec<PathBuf> = cfg.paths();
+    let mut dirs: DirTree<DirSize> = DirTree::new(&paths);
+    // Block size of each ext filesystem found, by its mount point
+    let mut block_sizes: HashMap<&Path, u64> = HashMap::new();
+
+    walk(cfg).filter_map(|e| e.ok()).for_each(|e: Entry| {
+        // The entries of directories at the maximum depth are not visited, so their required size
+        // is unknown
+        if e.file_type().is_dir() && e.depth() < cfg.max_depth() {
+            if let (Ok(record), Some(dir)) = (e.record(), dirs.get_mut(e.path())) {
+                dir.own = record.size;
+                dir.block_size = mount_of(e.path())
+                    .filter(|mount| mount.is_ext())
+                    .map(|mount| {
+                        let mount_point: &Path = &mount.mount_point;
+                        *block_sizes
+                            .entry(mount_point)
+                            .or_insert_with(|| block_size(mount_point))
+                    });
+            }
+        }
+        if let (true, Some(parent)) = (e.depth() > 0, e.path().parent()) {
+            if let Some(dir) = dirs.get_mut(parent) {
+                dir.add_entry(e.file_name());
+            }
+        }
+    });
+
+    let limit: usize = cfg.limit.unwrap_or(usize::MAX);
+    let bloated: Vec<u64> = dirs
+        .iter()
+        .filter(|(_, size)| size.is_bloated())
+        .filter_map(|(id, size)| Some((dirs.path(id), size, size.required()?)))
+        .sorted_by(|(path0, size0, _), (path1, size1, _)| {
+            size1.own.cmp(&size0.own).then_with(|| path0.cmp(path1))
+        })
+        .take(limit)
+        .inspect(|(path, size, required)| print_bloated(path, size, *required, cfg))
+        .map(|(_, size, _)| size.own)
+        .collect_vec();
+
+    (bloated.len() as u64, bloated.iter().sum())
 }
 
-fn create_walker(cfg: &Config, path: &Path) -> WalkDir {
-    let walker = WalkDir::new(path)
-        .follow_links(false)
-        .max_depth(cfg.max_depth())
-        .same_file_system(cfg.only_local_fs);
+fn block_size(root: &Path) -> u64 {
+    match fs_stats(root) {
+        Ok(stats) => stats.block_size,
+        Err(err) => {
+            log::warn!("Unable to obtain block size for {:?}: {}", root, err);
+            DEFAULT_BLOCK_SIZE
+        }
+    }
+}
 
-    log::debug!("walkdir: {:?}", walker);
-    walker
+fn size_of(entry: &Entry) -> Usage {
+    match entry.record() {
+        Ok(record) => Usage::of(&record),
+        Err(_) => Usage::default(),
+    }
 }
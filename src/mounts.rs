@@ -0,0 +1,445 @@
+use crate::cfg::Config;
+use itertools::Itertools;
+use lazy_static::lazy_static;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const MOUNTINFO: &str = "/proc/self/mountinfo";
+
+/// Pseudo and virtual filesystems which does not contain any files of interest, and which are
+/// skipped unless explicitly requested
+pub const DEFAULT_EXCLUDED_FS_TYPES: &[&str] = &[
+    "autofs",
+    "binfmt_misc",
+    "bpf",
+    "cgroup",
+    "cgroup2",
+    "configfs",
+    "debugfs",
+    "devpts",
+    "devtmpfs",
+    "efivarfs",
+    "fusectl",
+    "hugetlbfs",
+    "mqueue",
+    "nsfs",
+    "overlay",
+    "proc",
+    "pstore",
+    "rpc_pipefs",
+    "securityfs",
+    "selinuxfs",
+    "sysfs",
+    "tracefs",
+];
+
+/// Filesystems which are stored in memory rather than on disk
+pub const MEMORY_FS_TYPES: &[&str] = &["ramfs", "tmpfs"];
+
+/// The ext family of filesystems, which share the same layout of directory entries
+const EXT_FS_TYPES: &[&str] = &["ext2", "ext3", "ext4"];
+
+/// Network filesystems, which may be very slow to traverse or even hang when the server does not
+/// respond
+pub const REMOTE_FS_TYPES: &[&str] = &[
+    "9p",
+    "afs",
+    "beegfs",
+    "ceph",
+    "cifs",
+    "coda",
+    "davfs",
+    "fuse.cephfs",
+    "fuse.davfs2",
+    "fuse.gcsfuse",
+    "fuse.glusterfs",
+    "fuse.rclone",
+    "fuse.s3fs",
+    "fuse.sshfs",
+    "gfs2",
+    "glusterfs",
+    "gpfs",
+    "lustre",
+    "ncpfs",
+    "nfs",
+    "nfs4",
+    "smb3",
+    "smbfs",
+    "sshfs",
+];
+
+lazy_static! {
+    static ref MOUNTS: Vec<Mount> = match read_mounts() {
+        Ok(mounts) => mounts,
+        Err(err) => {
+            log::debug!("Unable to read mounts from {}: {}", MOUNTINFO, err);
+            Vec::with_capacity(0)
+        }
+    };
+}
+
+/// A mounted filesystem, as listed in /proc/self/mountinfo
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mount {
+    pub mount_point: PathBuf,
+    pub fs_type: String,
+    /// The mounted device or other source of the filesystem, such as a remote host
+    pub source: String,
+    /// The device number of the filesystem, which is the device of the files in it
+    pub dev: u64,
+}
+
+impl Mount {
+    pub fn is_root(&self) -> bool {
+        self.mount_point == Path::new("/")
+    }
+
+    pub fn is_remote(&self) -> bool {
+        REMOTE_FS_TYPES.contains(&self.fs_type.as_str())
+    }
+
+    pub fn is_in_memory(&self) -> bool {
+        MEMORY_FS_TYPES.contains(&self.fs_type.as_str())
+    }
+
+    pub fn is_ext(&self) -> bool {
+        EXT_FS_TYPES.contains(&self.fs_type.as_str())
+    }
+}
+
+/// All filesystems currently mounted. The list is only read once, and it is empty on platforms
+/// without /proc/self/mountinfo.
+pub fn mounts() -> &'static [Mount] {
+    &MOUNTS
+}
+
+fn read_mounts() -> io::Result<Vec<Mount>> {
+    let mountinfo: String = fs::read_to_string(MOUNTINFO)?;
+    Ok(mountinfo.lines().filter_map(parse_mount).collect())
+}
+
+/// Parse a line in mountinfo, which has the format
+/// `ID PARENT_ID MAJOR:MINOR ROOT MOUNT_POINT OPTIONS [OPTIONAL_FIELDS...] - FS_TYPE SOURCE
+/// SUPER_OPTIONS`
+fn parse_mount(line: &str) -> Option<Mount> {
+    let mut fields = line.split(' ');
+    let (major, minor) = fields.nth(2)?.split_once(':')?;
+    let dev: u64 = device(major.parse().ok()?, minor.parse().ok()?);
+    let mount_point: &str = fields.nth(1)?;
+    let mut fields = fields.skip_while(|field| *field != "-").skip(1);
+    let fs_type: &str = fields.next()?;
+    let source: &str = fields.next()?;
+
+    Some(Mount {
+        mount_point: PathBuf::from(unescape(mount_point)),
+        fs_type: fs_type.to_string(),
+        source: unescape(source),
+        dev,
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn device(major: u32, minor: u32) -> u64 {
+    libc::makedev(major, minor)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn device(_major: u32, _minor: u32) -> u64 {
+    0
+}
+
+/// Paths in mountinfo have space, tab, newline and backslash escaped as octal numbers, like `\040`
+fn unescape(field: &str) -> String {
+    let mut unescaped = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        let octal: String = chars.by_ref().take(3).collect();
+        match u8::from_str_radix(&octal, 8) {
+            Ok(byte) => unescaped.push(byte as char),
+            Err(_) => {
+                unescaped.push(c);
+                unescaped.push_str(&octal);
+            }
+        }
+    }
+    unescaped
+}
+
+/// The mount which contains the given path, i.e. the mount with the longest mount point that is
+/// a prefix of the path. The path is expected to be canonical.
+pub fn mount_of(path: &Path) -> Option<&'static Mount> {
+    containing_mount(mounts(), path)
+}
+
+/// When several filesystems are mounted on the same mount point, the one listed last is the
+/// visible one, which `max_by_key` picks since it returns the last of the largest elements
+fn containing_mount<'a>(mounts: &'a [Mount], path: &Path) -> Option<&'a Mount> {
+    mounts
+        .iter()
+        .filter(|m| path.starts_with(&m.mount_point))
+        .max_by_key(|m| m.mount_point.components().count())
+}
+
+/// The visible mount of the filesystem with the given device number, if it is mounted
+pub fn mount_of_dev(dev: u64) -> Option<&'static Mount> {
+    mounts().iter().rev().find(|m| m.dev == dev)
+}
+
+/// Mounts to skip when walking from a path
+#[derive(Debug, Default)]
+pub struct Pruned {
+    /// Mount points which should not be traversed by the walker, expressed as paths relative to the
+    /// walked path in the same way as the paths of the entries found when walking it
+    pub paths: HashSet<PathBuf>,
+    /// Mount points of remote filesystems which are pruned from the walker but which should be
+    /// walked separately, expressed in the same way as `paths`
+    pub remote: Vec<PathBuf>,
+}
+
+/// Decides which filesystems to traverse based on their type
+#[derive(Debug)]
+pub struct FsTypeFilter {
+    only: Vec<String>,
+    excluded: Vec<String>,
+    include_remote: bool,
+}
+
+impl FsTypeFilter {
+    pub fn accept(&self, mount: &Mount) -> bool {
+        // The root filesystem may very well be an overlay, as in a container
+        if mount.is_root() {
+            return true;
+        }
+        if !self.only.is_empty() {
+            return self.only.contains(&mount.fs_type);
+        }
+        !self.excluded.contains(&mount.fs_type)
+    }
+
+    /// Whether the files on a filesystem occupy space on disk which can be reclaimed, where
+    /// filesystems stored in memory are only included if their type is requested explicitly
+    pub fn on_disk(&self, mount: &Mount) -> bool {
+        self.accept(mount) && (!mount.is_in_memory() || self.only.contains(&mount.fs_type))
+    }
+
+    /// Mounted filesystems that are not excluded by type, i.e. filesystems with actual files
+    /// stored on them. Remote filesystems are only included if requested, since querying them may
+    /// hang if the server does not respond. When several filesystems are mounted on the same mount
+    /// point, only the last one, which is the one visible, is included.
+    pub fn real_mounts(&self) -> Vec<&'static Mount> {
+        let mut real: Vec<&Mount> = mounts()
+            .iter()
+            .rev()
+            .unique_by(|m| &m.mount_point)
+            .filter(|m| self.accept(m))
+            .filter(|m| self.include_remote || !m.is_remote())
+            .collect();
+        real.reverse();
+        real
+    }
+
+    /// Mounts which should not be traversed when walking from `root`. If `root` itself is on a
+    /// filesystem that should not be traversed, `root` is included in the pruned paths.
+    pub fn pruned(&self, root: &Path) -> Pruned {
+        let canonical: PathBuf = match root.canonicalize() {
+            Ok(canonical) => canonical,
+            Err(_) => return Pruned::default(),
+        };
+
+        let mut pruned = Pruned::default();
+        let relative = |m: &Mount| Some(root.join(m.mount_point.strip_prefix(&canonical).ok()?));
+
+        for mount in mounts() {
+            if !mount.mount_point.starts_with(&canonical) || mount.mount_point == canonical {
+                continue;
+            }
+            let path: PathBuf = match relative(mount) {
+                Some(path) => path,
+                None => continue,
+            };
+            if !self.accept(mount) {
+                log::info!(
+                    "Skipping {} mounted at {:?}",
+                    mount.fs_type,
+                    mount.mount_point
+                );
+                pruned.paths.insert(path);
+            } else if mount.is_remote() {
+                if self.include_remote {
+                    pruned.remote.push(path.clone());
+                } else {
+                    self.log_skipped_remote(mount);
+                }
+                pruned.paths.insert(path);
+            }
+        }
+
+        // Remote mounts below a mount that is skipped should be skipped as well
+        let skipped: Vec<PathBuf> = pruned
+            .paths
+            .iter()
+            .filter(|path| !pruned.remote.contains(path))
+            .cloned()
+            .collect();
+        pruned
+            .remote
+            .retain(|remote| !skipped.iter().any(|path| remote.starts_with(path)));
+
+        if let Some(mount) = mount_of(&canonical) {
+            if !self.accept(mount) {
+                log::warn!(
+                    "Skipping {:?}, since it is on a filesystem of type {}",
+                    root,
+                    mount.fs_type
+                );
+                pruned.paths.insert(root.to_path_buf());
+            } else if mount.is_remote() && !self.include_remote {
+                self.log_skipped_remote(mount);
+                pruned.paths.insert(root.to_path_buf());
+            }
+        }
+
+        pruned
+    }
+
+    /// Whether `path` is on a remote filesystem
+    pub fn is_remote(&self, path: &Path) -> bool {
+        match path.canonicalize() {
+            Ok(path) => matches!(mount_of(&path), Some(mount) if mount.is_remote()),
+            Err(_) => false,
+        }
+    }
+
+    fn log_skipped_remote(&self, mount: &Mount) {
+        log::warn!(
+            "Skipping remote filesystem {} mounted at {:?}, use --include-remote to include it",
+            mount.fs_type,
+            mount.mount_point
+        );
+    }
+}
+
+impl From<&Config> for FsTypeFilter {
+    fn from(cfg: &Config) -> Self {
+        let excluded: Vec<String> = DEFAULT_EXCLUDED_FS_TYPES
+            .iter()
+            .map(|fs_type| fs_type.to_string())
+            .chain(cfg.exclude_fs_types.iter().cloned())
+            .collect();
+
+        FsTypeFilter {
+            only: cfg.fs_types.clone(),
+            excluded,
+            include_remote: cfg.include_remote,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{containing_mount, parse_mount, unescape, FsTypeFilter, Mount};
+    use crate::cfg::Config;
+    use std::path::PathBuf;
+
+    fn mount(mount_point: &str, fs_type: &str) -> Mount {
+        Mount {
+            mount_point: PathBuf::from(mount_point),
+            fs_type: fs_type.to_string(),
+            source: String::from("none"),
+            dev: 0,
+        }
+    }
+
+    #[test]
+    fn test_parse_mount() {
+        let line = "36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw,errors=continue";
+        let expected = Mount {
+            mount_point: PathBuf::from("/mnt2"),
+            fs_type: String::from("ext3"),
+            source: String::from("/dev/root"),
+            dev: 98 << 8,
+        };
+        assert_eq!(Some(expected), parse_mount(line));
+    }
+
+    #[test]
+    fn test_parse_mount_without_optional_fields() {
+        let line = "23 28 0:22 / /proc rw,relatime - proc proc rw";
+        let expected = Mount {
+            source: String::from("proc"),
+            dev: 22,
+            ..mount("/proc", "proc")
+        };
+        assert_eq!(Some(expected), parse_mount(line));
+    }
+
+    #[test]
+    fn test_mount_of_path() {
+        let mounts = [
+            mount("/", "ext4"),
+            mount("/home", "xfs"),
+            mount("/home2", "btrfs"),
+        ];
+        let found = containing_mount(&mounts, &PathBuf::from("/home/user"));
+        assert_eq!(Some(&mounts[1]), found);
+    }
+
+    #[test]
+    fn test_last_mount_on_same_mount_point_is_visible() {
+        let mounts = [
+            mount("/", "ext4"),
+            mount("/dev/shm", "tmpfs"),
+            mount("/dev/shm", "ext4"),
+        ];
+        let found = containing_mount(&mounts, &PathBuf::from("/dev/shm/file"));
+        assert_eq!(Some(&mounts[2]), found);
+    }
+
+    #[test]
+    fn test_memory_filesystems_are_not_on_disk() {
+        let filter = FsTypeFilter::from(&Config::default());
+        assert!(filter.on_disk(&mount("/home", "ext4")));
+        assert!(!filter.on_disk(&mount("/dev/shm", "tmpfs")));
+        assert!(!filter.on_disk(&mount("/proc", "proc")));
+        let requested = FsTypeFilter::from(&Config::default().with_fs_types(&["tmpfs"]));
+        assert!(requested.on_disk(&mount("/dev/shm", "tmpfs")));
+    }
+
+    #[test]
+    fn test_unescape_space() {
+        assert_eq!("/mnt/my disk", unescape("/mnt/my\\040disk"));
+    }
+
+    #[test]
+    fn test_exclude_pseudo_filesystem_by_default() {
+        let filter: FsTypeFilter = (&Config::default()).into();
+        assert!(!filter.accept(&mount("/sys", "sysfs")));
+        assert!(filter.accept(&mount("/home", "ext4")));
+    }
+
+    #[test]
+    fn test_never_exclude_root_filesystem() {
+        let filter: FsTypeFilter = (&Config::default()).into();
+        assert!(filter.accept(&mount("/", "overlay")));
+    }
+
+    #[test]
+    fn test_classify_remote_filesystem() {
+        assert!(mount("/mnt/share", "nfs4").is_remote());
+        assert!(mount("/mnt/ssh", "fuse.sshfs").is_remote());
+        assert!(!mount("/home", "ext4").is_remote());
+    }
+
+    #[test]
+    fn test_only_given_fs_types() {
+        let filter: FsTypeFilter = (&Config::default().with_fs_types(&["sysfs"])).into();
+        assert!(filter.accept(&mount("/sys", "sysfs")));
+        assert!(!filter.accept(&mount("/home", "ext4")));
+    }
+}
@@ -0,0 +1,608 @@
+use crate::budget;
+use crate::cache::{CachedEntry, ScanCache};
+use crate::entry::{entry_type, Entry, Error};
+use crate::entry_type::EntryType;
+use crate::explore::{self, BiggestFirst};
+use crate::record::Record;
+use crate::sample::Sampled;
+use crate::throttle;
+use crate::walk::{self, WalkOptions, WalkResult};
+use crossbeam_deque::{Injector, Steal, Stealer, Worker};
+use itertools::Itertools;
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::fs::{self, FileType, Metadata};
+use std::iter;
+#[cfg(unix)]
+use std::os::unix::fs::DirEntryExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Number of entries which may be buffered before the walking threads have to wait for the
+/// consumer
+const BUFFER_SIZE: usize = 4096;
+
+/// Time to wait before looking for more work, when there is no directory to read at the moment
+/// but other threads are still reading directories which may contain more directories
+const IDLE_WAIT: Duration = Duration::from_micros(200);
+
+/// How often the consumer checks whether the search has been stopped, while it is waiting for an
+/// entry
+const STOP_WAIT: Duration = Duration::from_millis(100);
+
+/// A directory which has been found but not yet read
+struct Dir {
+    path: PathBuf,
+    depth: usize,
+    /// Used to find the cached entries of the directory
+    record: Option<Record>,
+    /// Estimated size of the files in the directory and its subdirectories, see [BiggestFirst]
+    estimate: u64,
+    /// Set when the contents of directories are sent first
+    subtree: Option<Arc<Subtree>>,
+}
+
+/// A directory whose entry is only sent once it and all its subdirectories have been read, when
+/// the contents of directories are sent first
+struct Subtree {
+    entry: Mutex<Option<Entry>>,
+    /// Number of subdirectories which have not been completely read, plus one until the directory
+    /// itself has been read
+    remaining: AtomicUsize,
+    parent: Option<Arc<Subtree>>,
+}
+
+impl Subtree {
+    fn new(entry: Entry, parent: Option<Arc<Subtree>>) -> Arc<Subtree> {
+        if let Some(parent) = &parent {
+            parent.remaining.fetch_add(1, Ordering::AcqRel);
+        }
+        Arc::new(Subtree {
+            entry: Mutex::new(Some(entry)),
+            remaining: AtomicUsize::new(1),
+            parent,
+        })
+    }
+}
+
+/// What the walking threads send to the consumer
+enum Message {
+    Entry(WalkResult),
+    /// All the entries of the directory at this path have been sent, which is only sent when the
+    /// directories which have not been read are tracked
+    DirRead(PathBuf),
+}
+
+/// State shared between all threads of a walk
+struct Shared {
+    options: WalkOptions,
+    injector: Injector<Dir>,
+    stealers: Vec<Stealer<Dir>>,
+    /// Used instead of the queues of the threads when the largest directories are read first
+    biggest_first: Option<BiggestFirst<Dir>>,
+    /// Number of directories which have been queued but which have not been completely read yet.
+    /// The walk is finished once this reaches zero.
+    pending: AtomicUsize,
+    /// Set when the consumer has stopped receiving entries
+    stopped: AtomicBool,
+    root_dev: Option<u64>,
+    /// Paths of the directories which have been queued but whose entries have not all been
+    /// received by the consumer, when they are tracked
+    unvisited: Option<Mutex<HashSet<PathBuf>>>,
+}
+
+/// Walks a directory tree with several threads, where each thread reads one directory at a time
+/// and idle threads steal directories queued by other threads. Entries are produced in no
+/// particular order, but each entry is produced exactly once.
+///
+/// Since the metadata of each entry is obtained by the walking threads, the latency of each call
+/// to `stat` is hidden as well, which matters on network filesystems and fast disks where the
+/// walk is limited by the number of calls rather than the bandwidth.
+///
+/// When reading in inode order, all the entries of a directory are read and sorted by inode
+/// number before any of them is stat'ed, and subdirectories are read in the same order, like
+/// `ncdu` and `rdfind` do. Inodes are typically laid out on disk in the order of their numbers, so
+/// this reduces seeking on spinning disks.
+///
+/// When exploring the biggest directories first, all threads take directories from a single
+/// queue ordered by their estimated size instead, see [BiggestFirst].
+///
+/// When the contents of directories are sent first, the entry of a directory is held back until
+/// the directory and all its subdirectories have been read, like `WalkDir::contents_first`. The
+/// thread which reads the last directory of a subtree sends the entry of the subtree, and so on
+/// up to the root.
+///
+/// When the directories which have not been read are tracked, a directory is only counted as read
+/// once the consumer has received all its entries, and those that remain when the walk is dropped
+/// after the search was stopped are added to [budget::unvisited].
+pub struct ParallelWalk {
+    receiver: Receiver<Message>,
+    shared: Option<Arc<Shared>>,
+}
+
+impl ParallelWalk {
+    pub fn new(root: &Path, options: WalkOptions, threads: usize) -> ParallelWalk {
+        let (sender, receiver) = mpsc::sync_channel(BUFFER_SIZE);
+        let root_entry: Entry = match root_entry(root, &options) {
+            Ok(entry) => entry,
+            Err(err) => {
+                let _ = sender.send(Message::Entry(Err(err)));
+                return ParallelWalk::done(receiver);
+            }
+        };
+        if options.pruned.contains(root) || !options.visited.first_visit(&root_entry) {
+            return ParallelWalk::done(receiver);
+        }
+
+        let descend: bool = is_dir(root) && options.max_depth > 0;
+        let root_dev: Option<u64> = match options.only_local_fs {
+            true => root_entry.record().ok().map(|r| r.dev),
+            false => None,
+        };
+        let subtree: Option<Arc<Subtree>> = match options.contents_first && descend {
+            true => Some(Subtree::new(root_entry, None)),
+            false => {
+                if sender.send(Message::Entry(Ok(root_entry))).is_err() || !descend {
+                    return ParallelWalk::done(receiver);
+                }
+                None
+            }
+        };
+        // The root is descended into even if it is a link to a directory, so the entries are
+        // cached for the directory rather than for the link
+        let root_record: Option<Record> = Record::stat(root, true).ok();
+
+        let workers: Vec<Worker<Dir>> = (0..threads).map(|_| Worker::new_lifo()).collect();
+        let biggest_first: Option<BiggestFirst<Dir>> =
+            options.biggest_first.then(BiggestFirst::new);
+        let unvisited: Option<Mutex<HashSet<PathBuf>>> =
+            options.track_unvisited.then(Mutex::default);
+        let shared = Arc::new(Shared {
+            options,
+            injector: Injector::new(),
+            stealers: workers.iter().map(|w| w.stealer()).collect(),
+            biggest_first,
+            pending: AtomicUsize::new(1),
+            stopped: AtomicBool::new(false),
+            root_dev,
+            unvisited,
+        });
+        let root_dir = Dir {
+            path: root.to_path_buf(),
+            depth: 0,
+            record: root_record,
+            estimate: 0,
+            subtree,
+        };
+        shared.track(&root_dir);
+        match &shared.biggest_first {
+            Some(queue) => queue.push(0, 0, root_dir),
+            None => shared.injector.push(root_dir),
+        }
+
+        for worker in workers {
+            let shared = shared.clone();
+            let sender = sender.clone();
+            thread::spawn(move || shared.run(worker, sender));
+        }
+
+        ParallelWalk {
+            receiver,
+            shared: Some(shared),
+        }
+    }
+
+    /// A walk which produces no more entries than those already sent
+    fn done(receiver: Receiver<Message>) -> ParallelWalk {
+        ParallelWalk {
+            receiver,
+            shared: None,
+        }
+    }
+
+    /// Stop keeping track of a directory whose entries have all been received
+    fn dir_read(&self, path: &Path) {
+        let unvisited = self.shared.as_ref().and_then(|s| s.unvisited.as_ref());
+        if let Some(unvisited) = unvisited {
+            unvisited.lock().unwrap().remove(path);
+        }
+    }
+}
+
+impl Iterator for ParallelWalk {
+    type Item = WalkResult;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // The walk is stopped along with the search even if no entry is found for a while, such as
+        // when a directory on a slow filesystem is being read
+        loop {
+            match self.receiver.recv_timeout(STOP_WAIT) {
+                Ok(Message::Entry(result)) => return Some(result),
+                Ok(Message::DirRead(path)) => self.dir_read(&path),
+                Err(RecvTimeoutError::Timeout) if !walk::stopped() => continue,
+                Err(_) => return None,
+            }
+        }
+    }
+}
+
+impl Drop for ParallelWalk {
+    fn drop(&mut self) {
+        let unvisited = self.shared.as_ref().and_then(|s| s.unvisited.as_ref());
+        if let (Some(unvisited), true) = (unvisited, walk::stopped()) {
+            budget::add_unvisited(unvisited.lock().unwrap().iter().cloned());
+        }
+    }
+}
+
+impl Shared {
+    fn run(&self, local: Worker<Dir>, sender: SyncSender<Message>) {
+        while !self.stopped.load(Ordering::Relaxed) && !walk::stopped() {
+            match self.find_dir(&local) {
+                Some(dir) => {
+                    if self.read_dir(&dir, &local, &sender) && self.unvisited.is_some() {
+                        let _ = sender.send(Message::DirRead(dir.path.clone()));
+                    }
+                    self.complete(dir.subtree, &sender);
+                    self.pending.fetch_sub(1, Ordering::AcqRel);
+                }
+                None if self.pending.load(Ordering::Acquire) == 0 => break,
+                None => thread::sleep(IDLE_WAIT),
+            }
+        }
+    }
+
+    /// Take a directory from the thread's own queue, or steal one from the global queue or from
+    /// another thread if the own queue is empty
+    fn find_dir(&self, local: &Worker<Dir>) -> Option<Dir> {
+        if let Some(queue) = &self.biggest_first {
+            return queue.pop();
+        }
+        local.pop().or_else(|| {
+            iter::repeat_with(|| {
+                self.injector
+                    .steal_batch_and_pop(local)
+                    .or_else(|| self.stealers.iter().map(|s| s.steal()).collect())
+            })
+            .find(|s: &Steal<Dir>| !s.is_retry())
+            .and_then(|s| s.success())
+        })
+    }
+
+    /// Send the entries of a directory and queue its subdirectories. Returns false if the walk was
+    /// stopped before all entries were sent.
+    fn read_dir(&self, dir: &Dir, local: &Worker<Dir>, sender: &SyncSender<Message>) -> bool {
+        let depth: usize = dir.depth + 1;
+        let follow_links: bool = self.options.follow_links;
+        let cache: Option<&ScanCache> = self.options.cache.as_deref();
+        if let (Some(cache), Some(record)) = (cache, &dir.record) {
+            if let Some(cached) = cache.lookup(record, follow_links) {
+                let entries = cached
+                    .iter()
+                    .filter(|c| !self.options.pruned.contains(&dir.path.join(&c.name)))
+                    .map(|c| self.cached_entry(&dir.path, depth, c));
+                return self.visit(dir, entries, local, sender);
+            }
+        }
+
+        throttle::before_read_dir();
+        let read_dir = match fs::read_dir(&dir.path) {
+            Ok(read_dir) => read_dir,
+            Err(err) => {
+                self.send(sender, Err(Error::new(&dir.path, err)));
+                return true;
+            }
+        };
+        // The entries are only cached if all of them could be read
+        let complete: Cell<bool> = Cell::new(true);
+        let mut cached: Vec<CachedEntry> = Vec::new();
+        let children = read_dir.filter_map(|child| match child {
+            Ok(child) => Some(child),
+            Err(err) => {
+                complete.set(false);
+                self.send(sender, Err(Error::new(&dir.path, err)));
+                None
+            }
+        });
+        let children: Box<dyn Iterator<Item = fs::DirEntry>> = match self.options.inode_order {
+            true => Box::new(children.sorted_by_key(inode)),
+            false => Box::new(children),
+        };
+        let children: Box<dyn Iterator<Item = (fs::DirEntry, Option<Sampled>)>> =
+            match &self.options.sampler {
+                Some(sampler) => {
+                    let children: Vec<fs::DirEntry> = children.collect();
+                    let count: usize = children.len();
+                    let sampled = sampler.sample(&dir.path, children, follow_links);
+                    // A directory where only some of the files were stat'ed is not cached
+                    if sampled.len() < count {
+                        complete.set(false);
+                    }
+                    Box::new(sampled.into_iter())
+                }
+                None => Box::new(children.map(|child| (child, None))),
+            };
+        let entries = children.filter_map(|(child, sample)| {
+            let path: PathBuf = child.path();
+            if self.options.pruned.contains(&path) {
+                complete.set(false);
+                return None;
+            }
+            let entry: Result<Entry, Error> = self
+                .entry(path, depth, &child)
+                .map(|entry| entry.with_sample(sample));
+            match (&entry, cache) {
+                (Ok(entry), Some(_)) => match CachedEntry::of(entry) {
+                    Some(entry) => cached.push(entry),
+                    None => complete.set(false),
+                },
+                (Err(_), _) => complete.set(false),
+                (Ok(_), None) => (),
+            }
+            Some(entry)
+        });
+        let finished: bool = self.visit(dir, entries, local, sender);
+
+        let complete: bool = finished && complete.get();
+        if let (true, Some(cache), Some(record)) = (complete, cache, &dir.record) {
+            cache.store(&dir.path, record, follow_links, cached);
+        }
+        finished
+    }
+
+    /// Send the entries of a directory and queue its subdirectories to be read. Returns false if
+    /// the walk was stopped before all entries were sent.
+    fn visit<I>(
+        &self,
+        dir: &Dir,
+        entries: I,
+        local: &Worker<Dir>,
+        sender: &SyncSender<Message>,
+    ) -> bool
+    where
+        I: Iterator<Item = Result<Entry, Error>>,
+    {
+        let defer: bool = self.options.inode_order || self.biggest_first.is_some();
+        let mut subdirs: Vec<Dir> = Vec::new();
+        // Size of the files directly in the directory
+        let mut size: u64 = 0;
+        for entry in entries {
+            if self.stopped.load(Ordering::Relaxed) || walk::stopped() {
+                return false;
+            }
+            let entry: Entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    self.send(sender, Err(err));
+                    continue;
+                }
+            };
+            if !self.options.visited.first_visit(&entry) {
+                continue;
+            }
+            if self.descend(&entry) {
+                self.pending.fetch_add(1, Ordering::AcqRel);
+                let mut subdir = Dir {
+                    path: entry.path().to_path_buf(),
+                    depth: entry.depth(),
+                    record: entry.record().ok(),
+                    estimate: 0,
+                    subtree: None,
+                };
+                self.track(&subdir);
+                if let Some(parent) = &dir.subtree {
+                    subdir.subtree = Some(Subtree::new(entry, Some(parent.clone())));
+                } else {
+                    self.send(sender, Ok(entry));
+                }
+                match defer {
+                    true => subdirs.push(subdir),
+                    false => local.push(subdir),
+                }
+                continue;
+            }
+            if !entry.file_type().is_dir() {
+                size += entry.record().map_or(0, |record| record.size);
+            }
+            self.send(sender, Ok(entry));
+        }
+        match &self.biggest_first {
+            Some(queue) => self.queue_biggest_first(queue, dir.estimate + size, subdirs),
+            // The directory pushed last is the first one to be read by this thread
+            None => subdirs
+                .into_iter()
+                .rev()
+                .for_each(|subdir| local.push(subdir)),
+        }
+        true
+    }
+
+    /// Count a directory as read, and send the entries of the directories whose subtrees have now
+    /// been completely read
+    fn complete(&self, subtree: Option<Arc<Subtree>>, sender: &SyncSender<Message>) {
+        let mut current: Option<Arc<Subtree>> = subtree;
+        while let Some(subtree) = current {
+            if subtree.remaining.fetch_sub(1, Ordering::AcqRel) != 1 {
+                return;
+            }
+            if let Some(entry) = subtree.entry.lock().unwrap().take() {
+                self.send(sender, Ok(entry));
+            }
+            current = subtree.parent.clone();
+        }
+    }
+
+    /// Queue the subdirectories of a directory with their share of the estimated size of the
+    /// directory, or with the size of their own files if that is known from the cache and larger
+    fn queue_biggest_first(&self, queue: &BiggestFirst<Dir>, estimate: u64, subdirs: Vec<Dir>) {
+        let weights: Vec<u64> = subdirs
+            .iter()
+            .map(|subdir| subdir.record.as_ref().map_or(1, explore::weight))
+            .collect();
+        let shares: Vec<u64> = explore::split(estimate, &weights);
+        for (mut subdir, share) in subdirs.into_iter().zip(shares) {
+            subdir.estimate = share.max(self.cached_size(&subdir));
+            queue.push(subdir.depth, subdir.estimate, subdir);
+        }
+    }
+
+    /// The size of the files directly in a directory, according to the cache
+    fn cached_size(&self, dir: &Dir) -> u64 {
+        let cache: Option<&ScanCache> = self.options.cache.as_deref();
+        let cached: Option<&[CachedEntry]> = match (cache, &dir.record) {
+            (Some(cache), Some(record)) => cache.lookup(record, self.options.follow_links),
+            _ => None,
+        };
+        cached
+            .unwrap_or_default()
+            .iter()
+            .filter(|entry| !entry.file_type.is_dir())
+            .map(|entry| entry.record.size)
+            .sum()
+    }
+
+    /// The entry for a cached entry of a directory which has not been modified since it was
+    /// cached. Subdirectories are stat'ed again, since their own entries may have been modified,
+    /// and so are symbolic links which are followed, since their targets may have changed.
+    fn cached_entry(&self, dir: &Path, depth: usize, cached: &CachedEntry) -> Result<Entry, Error> {
+        let path: PathBuf = dir.join(&cached.name);
+        let follow: bool = self.options.follow_links;
+        if cached.file_type.is_dir() || (cached.is_symlink && follow) {
+            let record: Record =
+                Record::stat(&path, follow).map_err(|err| Error::new(&path, err))?;
+            let file_type: EntryType = record.entry_type.unwrap_or(EntryType::File);
+            return Ok(Entry::new(
+                path,
+                file_type,
+                depth,
+                cached.is_symlink,
+                follow,
+                record,
+            ));
+        }
+        let (file_type, record) = (cached.file_type, cached.record);
+        Ok(Entry::new(
+            path,
+            file_type,
+            depth,
+            cached.is_symlink,
+            follow,
+            record,
+        ))
+    }
+
+    fn entry(&self, path: PathBuf, depth: usize, child: &fs::DirEntry) -> Result<Entry, Error> {
+        // The type is usually given when reading the directory, without a call to stat
+        let file_type: FileType = child.file_type().map_err(|err| Error::new(&path, err))?;
+        let is_symlink: bool = file_type.is_symlink();
+        if self.options.follow_links && is_symlink {
+            let metadata: Metadata = fs::metadata(&path).map_err(|err| Error::new(&path, err))?;
+            let file_type: EntryType = entry_type(metadata.file_type());
+            let record = Record::from(&metadata);
+            return Ok(Entry::new(path, file_type, depth, true, true, record));
+        }
+        let record: Record = Record::stat(&path, false).map_err(|err| Error::new(&path, err))?;
+        let file_type: EntryType = entry_type(file_type);
+        let follow: bool = self.options.follow_links;
+        Ok(Entry::new(
+            path, file_type, depth, is_symlink, follow, record,
+        ))
+    }
+
+    fn descend(&self, entry: &Entry) -> bool {
+        if !entry.file_type().is_dir() || entry.depth() >= self.options.max_depth {
+            return false;
+        }
+        match (self.root_dev, entry.record()) {
+            (Some(root_dev), Ok(record)) => record.dev == root_dev,
+            _ => true,
+        }
+    }
+
+    /// Keep track of a directory which has been queued, if unvisited directories are tracked
+    fn track(&self, dir: &Dir) {
+        if let Some(unvisited) = &self.unvisited {
+            unvisited.lock().unwrap().insert(dir.path.clone());
+        }
+    }
+
+    fn send(&self, sender: &SyncSender<Message>, result: WalkResult) {
+        if sender.send(Message::Entry(result)).is_err() {
+            self.stopped.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// The entry for the path a walk starts from. Like any other entry, it is only reported as the
+/// target of a symbolic link if links are followed, but it is always descended into if it is a
+/// link to a directory.
+fn root_entry(root: &Path, options: &WalkOptions) -> Result<Entry, Error> {
+    let metadata: Metadata = fs::symlink_metadata(root).map_err(|err| Error::new(root, err))?;
+    let is_symlink: bool = metadata.file_type().is_symlink();
+    let metadata: Metadata = match options.follow_links && is_symlink {
+        true => fs::metadata(root).map_err(|err| Error::new(root, err))?,
+        false => metadata,
+    };
+    let file_type: EntryType = entry_type(metadata.file_type());
+    let record = Record::from(&metadata);
+    let root: PathBuf = root.to_path_buf();
+    let follow: bool = options.follow_links;
+    Ok(Entry::new(root, file_type, 0, is_symlink, follow, record))
+}
+
+/// The inode number of an entry, as given when reading the directory
+#[cfg(unix)]
+fn inode(entry: &fs::DirEntry) -> u64 {
+    entry.ino()
+}
+
+#[cfg(not(unix))]
+fn inode(_entry: &fs::DirEntry) -> u64 {
+    0
+}
+
+fn is_dir(path: &Path) -> bool {
+    fs::metadata(path).map(|m| m.is_dir()).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cfg::Config;
+    use crate::entry::Entry;
+    use crate::walk::{walk, walked_paths};
+
+    const TEST_DIR: &str = "test_dirs";
+
+    #[test]
+    fn test_parallel_walk_finds_same_entries() {
+        let cfg = |threads: usize| Config::default().with_path(TEST_DIR).with_threads(threads);
+        assert_eq!(5, walked_paths(&cfg(4)).len());
+        assert_eq!(walked_paths(&cfg(1)), walked_paths(&cfg(4)));
+    }
+
+    #[test]
+    fn test_inode_order_walk_finds_same_entries() {
+        let cfg = || Config::default().with_path(TEST_DIR);
+        assert_eq!(
+            walked_paths(&cfg()),
+            walked_paths(&cfg().with_inode_order())
+        );
+    }
+
+    #[test]
+    fn test_contents_of_directories_first() {
+        for threads in [1, 4] {
+            let cfg = || Config::default().with_path(TEST_DIR).with_threads(threads);
+            let entries: Vec<Entry> = walk(&cfg().with_dirs()).filter_map(|e| e.ok()).collect();
+            for (i, entry) in entries.iter().enumerate() {
+                let below = |e: &Entry| e.path().starts_with(entry.path());
+                assert!(!entries[i + 1..].iter().any(below), "{:?}", entry.path());
+            }
+            assert_eq!(walked_paths(&cfg()), walked_paths(&cfg().with_dirs()));
+        }
+    }
+}
@@ -8,6 +8,37 @@ lazy_static! {
 
 pub fn digest(input: &str) -> Option<(u64, Option<char>)> {
     let num: u64 = NUM.find(input)?.as_str().parse().ok()?;
-    let chr: Option<char> = CHR.find(input).map(|i| i.as_str().chars().next()).flatten();
+    let chr: Option<char> = CHR.find(input).and_then(|i| i.as_str().chars().next());
     Some((num, chr))
 }
+
+pub fn parse_percent(input: &str) -> Result<f64, &str> {
+    let percent: f64 = input
+        .trim_end_matches('%')
+        .parse()
+        .map_err(|_| "Unable to parse percentage")?;
+    match (0.0..=100.0).contains(&percent) {
+        true => Ok(percent),
+        false => Err("Percentage is out of range (0 - 100)"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_percent;
+
+    #[test]
+    fn test_percent_with_sign() {
+        assert_eq!(Ok(85.0), parse_percent("85%"));
+    }
+
+    #[test]
+    fn test_percent_without_sign() {
+        assert_eq!(Ok(92.5), parse_percent("92.5"));
+    }
+
+    #[test]
+    fn test_percent_out_of_range() {
+        assert!(parse_percent("101%").is_err());
+    }
+}
@@ -1,66 +1,652 @@
+use crate::bloat::DirSize;
+use crate::budget::{self, Exhausted};
+use crate::cache::Collected;
 use crate::cfg::{Config, Mode};
+use crate::entry::Entry;
+use crate::entry_type::EntryType;
+#[cfg(target_os = "linux")]
+use crate::extent::Extents;
+use crate::filesystem::{fs_stats, FsStats};
+use crate::interrupt::interrupted;
+use crate::links::BrokenLink;
+use crate::mounts::{mount_of, Mount};
+use crate::proc::{DeletedFile, Opener, Process};
+use crate::progress::{self, Snapshot};
+use crate::remote::incomplete;
+use crate::slack::{Distribution, Slack};
+use crate::stats::Stats;
+use crate::usage::{Measure, Usage};
 use humansize::{file_size_opts as options, FileSize};
 use itertools::Itertools;
-use std::path::{Path, PathBuf};
-use walkdir::DirEntry;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
 
-pub fn print_file(entry: &DirEntry, cfg: &Config) {
+pub fn print_file(entry: &Entry, openers: Option<&[Opener]>, cfg: &Config) {
+    progress::found();
     let file: &Path = entry.path();
-    let size: u64 = match file.metadata() {
-        Ok(metadata) => metadata.len(),
+    let usage: Usage = match entry.record() {
+        Ok(record) => Usage::of(&record),
         Err(err) => {
             log_error(err, file);
             return;
         }
     };
     if cfg.plumbing_mode {
-        print_plumbing(file, size)
+        print_plumbing(file, usage, None, None, openers, cfg)
     } else {
-        print_porcelain(file, size)
+        let entry_type: Option<EntryType> = match cfg.types.is_empty() {
+            true => None,
+            false => Some(entry.file_type()),
+        };
+        print_porcelain(file, usage, None, None, entry_type, openers, cfg)
     }
 }
 
-pub fn print_dir(dir: &Path, size: u64, cfg: &Config) {
+/// Print a directory with the combined size of the files in it, and the size of the directory
+/// itself if asked for. If the size is estimated, it is given with the margin of its 95%
+/// confidence interval.
+pub fn print_dir(dir: &Path, usage: Usage, own_size: u64, margin: Option<u64>, cfg: &Config) {
+    progress::found();
+    let own_size: Option<u64> = Some(own_size).filter(|_| cfg.own_size);
     if cfg.plumbing_mode {
-        print_plumbing(dir, size)
+        print_plumbing(dir, usage, margin, own_size, None, cfg)
     } else {
-        print_porcelain(dir, size)
+        print_porcelain(dir, usage, margin, own_size, None, None, cfg)
     }
 }
 
-fn print_porcelain(file: &Path, size: u64) {
-    if let Some(path) = fmt_path(file, 0) {
-        if let Ok(size) = size.file_size(options::CONVENTIONAL) {
-            println!("{:>10} │ {}", size, path);
+fn print_porcelain(
+    file: &Path,
+    usage: Usage,
+    margin: Option<u64>,
+    own_size: Option<u64>,
+    entry_type: Option<EntryType>,
+    openers: Option<&[Opener]>,
+    cfg: &Config,
+) {
+    let path: String = match fmt_path(file, 0) {
+        Some(path) => path,
+        None => return,
+    };
+    let mut columns: Vec<String> = Vec::with_capacity(6);
+    if let Some(entry_type) = entry_type {
+        columns.push(entry_type.indicator().to_string());
+    }
+    let sparse: &str = if cfg.both_sizes && usage.is_sparse() {
+        " (sparse)"
+    } else {
+        ""
+    };
+    if cfg.both_sizes {
+        columns.push(format!("{:>10}", human(usage.apparent)));
+        columns.push(format!("{:>10}", human(usage.allocated)));
+    } else {
+        columns.push(format!("{:>10}", human(usage.get(cfg.measure()))));
+    }
+    if let Some(margin) = margin {
+        columns.push(format!("± {:>10}", human(margin)));
+    }
+    if let Some(own_size) = own_size {
+        columns.push(format!("{:>10} own", human(own_size)));
+    }
+    columns.push(format!("{}{}", path, sparse));
+    if let Some(openers) = openers.filter(|o| !o.is_empty()) {
+        let openers: String = openers
+            .iter()
+            .map(|o| {
+                let mode: &str = if o.writing { "writing" } else { "reading" };
+                format!("{} {} ({})", o.process.pid, o.process.command, mode)
+            })
+            .join(", ");
+        columns.push(openers);
+    }
+    println!("{}", columns.join(" │ "));
+}
+
+fn print_plumbing(
+    dir: &Path,
+    usage: Usage,
+    margin: Option<u64>,
+    own_size: Option<u64>,
+    openers: Option<&[Opener]>,
+    cfg: &Config,
+) {
+    let dir: PathBuf = match canonical(dir) {
+        Some(dir) => dir,
+        None => return,
+    };
+    let dir: &str = match dir.as_os_str().to_str() {
+        Some(dir) => dir,
+        None => return,
+    };
+    let mut columns: Vec<String> = Vec::with_capacity(4);
+    if cfg.both_sizes {
+        columns.push(usage.apparent.to_string());
+        columns.push(usage.allocated.to_string());
+    } else {
+        columns.push(usage.get(cfg.measure()).to_string());
+    }
+    if let Some(margin) = margin {
+        columns.push(margin.to_string());
+    }
+    if let Some(own_size) = own_size {
+        columns.push(own_size.to_string());
+    }
+    if let Some(openers) = openers {
+        let openers: String = openers
+            .iter()
+            .map(|o| {
+                let mode: char = if o.writing { 'w' } else { 'r' };
+                let command: String = escape_field(&o.process.command);
+                format!("{}:{}:{}", o.process.pid, command, mode)
+            })
+            .join(" ");
+        match openers.is_empty() {
+            true => columns.push(String::from("-")),
+            false => columns.push(openers),
         }
     }
+    columns.push(dir.to_string());
+    println!("{}", columns.join(", "));
 }
 
-fn print_plumbing(dir: &Path, size: u64) {
-    if let Some(dir) = canonical(dir) {
-        if let Some(dir) = dir.as_os_str().to_str() {
-            println!("{}, {}", size, dir)
+/// Percent-encode the characters which separate columns and fields in plumbing mode, so that a
+/// field can be split unambiguously
+fn escape_field(field: &str) -> String {
+    field
+        .chars()
+        .map(|c| match c {
+            '%' | ',' | ' ' | ':' => format!("%{:02X}", c as u32),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+pub fn print_extents(file: &Path, usage: Usage, extents: &Extents, cfg: &Config) {
+    progress::found();
+    let size: u64 = usage.get(cfg.measure());
+    if cfg.plumbing_mode {
+        if let Some(file) = canonical(file) {
+            if let Some(file) = file.as_os_str().to_str() {
+                println!(
+                    "{}, {}, {}, {}, {}, {}",
+                    size, extents.count, extents.shared, extents.unwritten, extents.holes, file
+                )
+            }
         }
+    } else if let Some(path) = fmt_path(file, 0) {
+        println!(
+            "{:>10} │ {:>8} extents │ {:>10} shared │ {:>10} unwritten │ {:>10} holes │ {}",
+            human(size),
+            extents.count,
+            human(extents.shared),
+            human(extents.unwritten),
+            human(extents.holes),
+            path
+        );
     }
 }
 
+#[cfg(target_os = "linux")]
+pub fn print_deleted(file: &DeletedFile, cfg: &Config) {
+    progress::found();
+    let size: u64 = Usage::of(&file.record).get(cfg.measure());
+    let path: String = file.path.to_string_lossy().to_string();
+    let process: &Process = &file.process;
+    if cfg.plumbing_mode {
+        println!(
+            "{}, {}, {}, {}",
+            size,
+            process.pid,
+            escape_field(&process.command),
+            path
+        )
+    } else {
+        println!(
+            "{:>10} │ {:>7} │ {:<15} │ {}",
+            human(size),
+            process.pid,
+            process.command,
+            path
+        );
+    }
+}
+
+pub fn print_broken_link(link: &BrokenLink, cfg: &Config) {
+    progress::found();
+    // The link itself can not be canonicalized, since its target does not exist
+    let path: PathBuf = match (
+        link.path.parent().and_then(canonical),
+        link.path.file_name(),
+    ) {
+        (Some(dir), Some(name)) => dir.join(name),
+        _ => link.path.clone(),
+    };
+    let path = path.to_string_lossy();
+    let target = link.target.to_string_lossy();
+    if cfg.plumbing_mode {
+        println!("{}, {}", path, target)
+    } else {
+        println!("{} → {}", path, target)
+    }
+}
+
+pub fn print_bloated(dir: &Path, size: &DirSize, required: u64, cfg: &Config) {
+    progress::found();
+    if cfg.plumbing_mode {
+        if let Some(dir) = canonical(dir) {
+            if let Some(dir) = dir.as_os_str().to_str() {
+                println!("{}, {}, {}, {}", size.own, required, size.entries, dir)
+            }
+        }
+    } else if let Some(path) = fmt_path(dir, 0) {
+        println!(
+            "{:>10} │ {:>10} required │ {:>10} entries │ {}",
+            human(size.own),
+            human(required),
+            size.entries,
+            path
+        );
+    }
+}
+
+pub fn print_count(dir: &Path, direct: u64, recursive: u64, cfg: &Config) {
+    progress::found();
+    if cfg.plumbing_mode {
+        if let Some(dir) = canonical(dir) {
+            if let Some(dir) = dir.as_os_str().to_str() {
+                println!("{}, {}, {}", recursive, direct, dir)
+            }
+        }
+    } else if let Some(path) = fmt_path(dir, 0) {
+        println!("{:>10} │ {:>10} │ {}", recursive, direct, path);
+    }
+}
+
+pub fn print_slack(dir: &Path, slack: Slack, cfg: &Config) {
+    progress::found();
+    if cfg.plumbing_mode {
+        if let Some(dir) = canonical(dir) {
+            if let Some(dir) = dir.as_os_str().to_str() {
+                println!(
+                    "{}, {}, {}, {}",
+                    slack.slack, slack.usage.apparent, slack.usage.allocated, dir
+                )
+            }
+        }
+    } else if let Some(path) = fmt_path(dir, 0) {
+        println!(
+            "{:>10} │ {:>10} │ {:>10} │ {}",
+            human(slack.slack),
+            human(slack.usage.apparent),
+            human(slack.usage.allocated),
+            path
+        );
+    }
+}
+
+/// Print the distribution of file sizes. This is only done in porcelain mode, to keep the output
+/// of plumbing mode consistent with other modes.
+pub fn print_distribution(distribution: &Distribution, cfg: &Config) {
+    progress::clear();
+    if cfg.plumbing_mode {
+        return;
+    }
+    println!();
+    println!(
+        "File sizes for a block size of {}",
+        human(distribution.block_size())
+    );
+    println!(
+        "{:>10} │ {:>10} │ {:>10} │ {:>10}",
+        "File size", "Files", "Apparent", "Slack"
+    );
+    for bucket in distribution.buckets() {
+        let bound: String = match bucket.upper_bound {
+            Some(0) => String::from("Empty"),
+            Some(bound) => format!("≤ {}", human(bound)),
+            None => String::from("Larger"),
+        };
+        println!(
+            "{:>10} │ {:>10} │ {:>10} │ {:>10}",
+            bound,
+            bucket.files,
+            human(bucket.usage.apparent),
+            human(bucket.slack)
+        );
+    }
+    println!();
+}
+
 pub fn print_summary(kind: Mode, found: u64, size: u64, cfg: &Config) {
+    progress::clear();
     if cfg.plumbing_mode {
         print_summary_plumbing(found, size)
+    } else if let Mode::Count = kind {
+        print_summary_count(found, size, cfg)
+    } else if let Mode::BrokenLinks = kind {
+        println!("Found {} broken symbolic links", found)
     } else {
-        print_summary_porcelain(kind, found, size)
+        print_summary_porcelain(kind, found, size, cfg.measure(), cfg.sample.is_some());
+        if let Mode::File | Mode::Dir | Mode::Extents | Mode::DeletedOpen = kind {
+            print_share(size, cfg)
+        }
+    }
+    print_incomplete(cfg);
+    if !cfg.plumbing_mode {
+        print_throttled(Stats::get().throttled);
     }
+    print_interrupted(kind, cfg);
+    print_exhausted(kind, cfg);
+    print_unvisited(cfg);
 }
 
-fn print_summary_porcelain(mode: Mode, found: u64, size: u64) {
+/// Print what share of the used space on the filesystem that was found, if all the searched paths
+/// are on the same filesystem
+fn print_share(size: u64, cfg: &Config) {
+    let mounts: Vec<&Mount> = cfg
+        .paths()
+        .iter()
+        .filter_map(|path| mount_of(&path.canonicalize().ok()?))
+        .unique_by(|mount| &mount.mount_point)
+        .collect();
+
+    if let [mount] = mounts[..] {
+        match fs_stats(&mount.mount_point) {
+            Ok(stats) if stats.used() > 0 => println!(
+                "This is {:.1}% of the {} used on the filesystem mounted at {}",
+                100.0 * (size as f64) / (stats.used() as f64),
+                human(stats.used()),
+                mount.mount_point.to_string_lossy()
+            ),
+            Ok(_) => (),
+            Err(err) => log_error(err, &mount.mount_point),
+        }
+    }
+}
+
+pub fn print_collected(collected: &Collected, cfg: &Config) {
+    if cfg.plumbing_mode {
+        println!(
+            "{}, {}, {}",
+            collected.kept, collected.removed, collected.size
+        );
+    } else {
+        println!(
+            "Removed {} stale directories from the cache, which now holds {} directories in {}",
+            collected.removed,
+            collected.kept,
+            human(collected.size)
+        );
+    }
+}
+
+pub fn print_mount(mount: &Mount, stats: &FsStats, cfg: &Config) {
+    let mount_point = mount.mount_point.to_string_lossy();
+    if cfg.plumbing_mode {
+        println!(
+            "{}, {}, {}, {}, {}, {}, {}, {}",
+            stats.size(),
+            stats.used(),
+            stats.available(),
+            stats.inodes,
+            stats.inodes_used(),
+            mount.fs_type,
+            mount.source,
+            mount_point
+        )
+    } else {
+        println!(
+            "{:>10} │ {:>10} │ {:>10} │ {:>5.1}% │ {:>10} │ {:>5.1}% │ {:<8} │ {}",
+            human(stats.size()),
+            human(stats.used()),
+            human(stats.available()),
+            stats.percent_used(),
+            stats.inodes,
+            stats.percent_inodes_used(),
+            mount.fs_type,
+            mount_point
+        )
+    }
+}
+
+fn print_summary_count(found: u64, entries: u64, cfg: &Config) {
+    println!(
+        "Found {} directories with a total of {} entries",
+        found, entries
+    );
+    let root: PathBuf = match cfg.paths().into_iter().next() {
+        Some(root) => root,
+        None => return,
+    };
+    match fs_stats(&root) {
+        Ok(stats) if stats.inodes > 0 => println!(
+            "This is {:.1}% of the {} inodes in use, the filesystem has {} of {} inodes free",
+            100.0 * (entries as f64) / (stats.inodes_used().max(1) as f64),
+            stats.inodes_used(),
+            stats.inodes_free,
+            stats.inodes,
+        ),
+        Ok(_) => log::info!("The filesystem does not report the number of inodes"),
+        Err(err) => log_error(err, &root),
+    }
+}
+
+/// List the remote filesystems which did not respond, which is done on stderr in plumbing mode so
+/// that the results can still be parsed
+fn print_incomplete(cfg: &Config) {
+    let incomplete: Vec<PathBuf> = incomplete();
+    if incomplete.is_empty() {
+        return;
+    }
+    if cfg.plumbing_mode {
+        incomplete
+            .iter()
+            .for_each(|path| eprintln!("Did not respond: {}", path.to_string_lossy()));
+    } else {
+        let paths: String = incomplete.iter().map(|p| p.to_string_lossy()).join(", ");
+        println!(
+            "Results are incomplete, since these filesystems did not respond: {}",
+            paths
+        );
+    }
+}
+
+fn print_interrupted(mode: Mode, cfg: &Config) {
+    if interrupted() {
+        print_partial("was interrupted", mode, cfg);
+    }
+}
+
+fn print_exhausted(mode: Mode, cfg: &Config) {
+    let reason: String = match budget::reason() {
+        Some(Exhausted::Timeout(timeout)) => {
+            format!("ran out of time after {}", fmt_duration(timeout))
+        }
+        Some(Exhausted::MaxEntries(entries)) => format!("was stopped after {} entries", entries),
+        None => return,
+    };
+    print_partial(&reason, mode, cfg);
+}
+
+/// Tell that the results are partial since the search was stopped, which is done on stderr in
+/// plumbing mode so that the results can still be parsed
+fn print_partial(reason: &str, mode: Mode, cfg: &Config) {
+    let notice: String = match mode {
+        Mode::Dir => format!(
+            "The search {}, so the results are partial, and directories which were not completely \
+            walked are listed last, sorted by path",
+            reason
+        ),
+        _ => format!("The search {}, so the results are partial", reason),
+    };
+    match cfg.plumbing_mode {
+        true => eprintln!("{}", notice),
+        false => println!("{}", notice),
+    }
+}
+
+/// List the directories which were not completely walked when the search was stopped, which is
+/// done on stderr in plumbing mode so that the results can still be parsed
+fn print_unvisited(cfg: &Config) {
+    let unvisited: Vec<PathBuf> = budget::unvisited();
+    if unvisited.is_empty() {
+        return;
+    }
+    if cfg.plumbing_mode {
+        unvisited
+            .iter()
+            .for_each(|path| eprintln!("Not walked: {}", path.to_string_lossy()));
+    } else {
+        println!("These directories were not completely walked:");
+        unvisited
+            .iter()
+            .for_each(|path| println!("  {}", path.to_string_lossy()));
+    }
+}
+
+fn print_throttled(throttled: Duration) {
+    if !throttled.is_zero() {
+        println!(
+            "The search was throttled for {:.1} s in total, summed over all threads",
+            throttled.as_secs_f64()
+        );
+    }
+}
+
+/// Print the largest directories found so far to stderr, so that they are kept apart from the
+/// results
+pub fn print_ranking(ranking: &[(PathBuf, u64)], elapsed: Duration, cfg: &Config) {
+    progress::clear();
+    if cfg.plumbing_mode {
+        for (dir, size) in ranking {
+            if let Some(dir) = canonical(dir).as_deref().and_then(Path::to_str) {
+                eprintln!("{}, {}, {}", elapsed.as_millis(), size, dir);
+            }
+        }
+        return;
+    }
+    eprintln!(
+        "Largest directories found after {:.0} s:",
+        elapsed.as_secs_f64()
+    );
+    for (dir, size) in ranking {
+        if let Some(dir) = fmt_path(dir, 0) {
+            eprintln!("{:>10} │ {}", human(*size), dir);
+        }
+    }
+}
+
+/// The progress of the search as a single line, which is kept short enough to fit on a terminal
+/// except for the current directory at the end of it
+pub fn fmt_progress(progress: &Snapshot, plumbing: bool) -> String {
+    let dir: String = progress.dir.to_string_lossy().to_string();
+    if plumbing {
+        let done: String = progress
+            .done
+            .map(|done| format!("{:.1}", 100.0 * done))
+            .unwrap_or_default();
+        return format!(
+            "{}, {}, {}, {}, {}, {:.0}, {}, {}",
+            progress.elapsed.as_millis(),
+            progress.entries,
+            progress.dirs,
+            progress.bytes,
+            progress.found,
+            progress.rate(),
+            done,
+            dir
+        );
+    }
+    let done: String = match (progress.done, progress.remaining()) {
+        (Some(done), Some(remaining)) => format!(
+            ", {:.0}% done, {} left",
+            100.0 * done,
+            fmt_duration(remaining)
+        ),
+        (Some(done), None) => format!(", {:.0}% done", 100.0 * done),
+        (None, _) => String::new(),
+    };
+    format!(
+        "{} entries, {} directories, {}, {} found, {:.0} entries/s{} │ {}",
+        progress.entries,
+        progress.dirs,
+        human(progress.bytes),
+        progress.found,
+        progress.rate(),
+        done,
+        dir
+    )
+}
+
+fn fmt_duration(duration: Duration) -> String {
+    let secs: u64 = duration.as_secs();
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m {}s", secs / 60, secs % 60),
+        _ => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+/// Print the statistics of the search to stderr, so that they are kept apart from the results
+pub fn print_stats(stats: &Stats, elapsed: Duration, cfg: &Config) {
+    progress::clear();
+    let secs: f64 = elapsed.as_secs_f64();
+    let per_call: Duration = stats
+        .stat_time
+        .checked_div(u32::try_from(stats.stat_calls).unwrap_or(u32::MAX))
+        .unwrap_or_default();
+    if cfg.plumbing_mode {
+        eprintln!(
+            "{}, {}, {}, {}, {}, {}",
+            stats.entries,
+            stats.dirs,
+            stats.errors,
+            stats.stat_calls,
+            stats.stat_time.as_micros(),
+            elapsed.as_micros()
+        );
+        return;
+    }
+    let rate: f64 = match secs > 0.0 {
+        true => stats.entries as f64 / secs,
+        false => 0.0,
+    };
+    eprintln!(
+        "Walked {} entries in {} directories in {:.2} s ({:.0} entries/s) with {} errors",
+        stats.entries, stats.dirs, secs, rate, stats.errors
+    );
+    eprintln!(
+        "Made {} calls to stat, which took {:.2} s in total and {:.1} µs per call",
+        stats.stat_calls,
+        stats.stat_time.as_secs_f64(),
+        per_call.as_secs_f64() * 1e6
+    );
+}
+
+fn print_summary_porcelain(mode: Mode, found: u64, size: u64, measure: Measure, estimated: bool) {
     let kind: &str = match mode {
-        Mode::File => "files",
-        Mode::Dir => "directories",
+        Mode::File | Mode::Extents | Mode::DeletedOpen => "files",
+        Mode::Dir | Mode::Count | Mode::Slack | Mode::Bloated => "directories",
+        Mode::BrokenLinks => "symbolic links",
+    };
+    let measure: &str = match (mode, measure) {
+        (Mode::Slack, _) => "slack",
+        (_, Measure::Apparent) => "size",
+        (_, Measure::Allocated) => "disk usage",
+    };
+    let total: &str = match estimated {
+        true => "an estimated total",
+        false => "a total",
     };
-    let human_size = size.file_size(options::CONVENTIONAL).unwrap();
     println!(
-        "Found {} {} with a total size of {}",
-        found, kind, human_size,
+        "Found {} {} with {} {} of {}",
+        found,
+        kind,
+        total,
+        measure,
+        human(size),
     );
 }
 
@@ -69,6 +655,10 @@ fn print_summary_plumbing(found: u64, size: u64) {
     println!("{}, {}", size, found)
 }
 
+fn human(size: u64) -> String {
+    size.file_size(options::CONVENTIONAL).unwrap()
+}
+
 fn fmt_path(path: &Path, root_level: usize) -> Option<String> {
     let skip = if root_level == 0 {
         root_level
@@ -89,7 +679,28 @@ fn fmt_path(path: &Path, root_level: usize) -> Option<String> {
     Some(formatted)
 }
 
+/// The canonical form of `path`. A path to a symbolic link is resolved to the path of the link
+/// itself rather than to the path of its target.
 fn canonical(path: &Path) -> Option<PathBuf> {
+    // Paths found when searching are already absolute, see [Config::paths]
+    if path.is_absolute()
+        && path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::RootDir))
+    {
+        return Some(path.to_path_buf());
+    }
+    let is_symlink: bool = match path.symlink_metadata() {
+        Ok(metadata) => metadata.file_type().is_symlink(),
+        Err(_) => false,
+    };
+    if let (true, Some(parent), Some(name)) = (is_symlink, path.parent(), path.file_name()) {
+        let parent: &Path = match parent.as_os_str().is_empty() {
+            true => Path::new("."),
+            false => parent,
+        };
+        return canonical(parent).map(|parent| parent.join(name));
+    }
     match path.canonicalize() {
         Ok(p) => Some(p),
         Err(e) => {
@@ -102,3 +713,14 @@ fn canonical(path: &Path) -> Option<PathBuf> {
 fn log_error(err: std::io::Error, path: &Path) {
     log::error!("Error when accessing file {:?}: {}", path, &err);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::escape_field;
+
+    #[test]
+    fn test_separators_are_escaped() {
+        assert_eq!("my%2Cproc%20a%3A1%25", escape_field("my,proc a:1%"));
+        assert_eq!("bash", escape_field("bash"));
+    }
+}
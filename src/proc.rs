@@ -0,0 +1,279 @@
+use crate::record::Record;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+#[cfg(target_os = "linux")]
+use std::{
+    ffi::OsStr,
+    fs::{self, DirEntry},
+    io,
+    path::Path,
+};
+
+#[cfg(target_os = "linux")]
+const PROC: &str = "/proc";
+#[cfg(target_os = "linux")]
+const DELETED_SUFFIX: &str = " (deleted)";
+
+/// Mask for the access mode in the flags of a file descriptor, see fcntl.h
+#[cfg(target_os = "linux")]
+const O_ACCMODE: u32 = 0o3;
+#[cfg(target_os = "linux")]
+const O_RDONLY: u32 = 0o0;
+
+/// A process which has a file open
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Process {
+    pub pid: u32,
+    pub command: String,
+}
+
+/// A process which has a specific file open, and whether it is open for writing or only for
+/// reading
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Opener {
+    pub process: Process,
+    pub writing: bool,
+}
+
+/// A file that has been deleted, but which is still kept open by a process and therefore still
+/// occupies space on disk.
+#[derive(Debug)]
+pub struct DeletedFile {
+    pub process: Process,
+    /// The path the file had before it was deleted
+    pub path: PathBuf,
+    pub record: Record,
+}
+
+#[cfg(target_os = "linux")]
+impl DeletedFile {
+    /// Identifies the inode of the file, which may be held open by several processes
+    pub fn inode(&self) -> (u64, u64) {
+        self.record.inode()
+    }
+}
+
+/// All regular files currently open by any process that we are permitted to inspect, indexed by
+/// device and inode.
+#[derive(Debug, Default)]
+pub struct OpenFiles {
+    files: HashMap<(u64, u64), Vec<Opener>>,
+}
+
+impl OpenFiles {
+    #[cfg(target_os = "linux")]
+    pub fn scan() -> OpenFiles {
+        let mut files: HashMap<(u64, u64), Vec<Opener>> = HashMap::new();
+        for process in processes() {
+            for fd in open_fds(&process) {
+                if !fd.record.is_file() {
+                    continue;
+                }
+                let inode = fd.record.inode();
+                let writing: bool = fd.writing();
+                let openers: &mut Vec<Opener> = files.entry(inode).or_default();
+                match openers.iter_mut().find(|o| o.process == process) {
+                    Some(opener) => opener.writing |= writing,
+                    None => openers.push(Opener {
+                        process: process.clone(),
+                        writing,
+                    }),
+                }
+            }
+        }
+        OpenFiles { files }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn scan() -> OpenFiles {
+        log::error!("Finding processes with open files is only supported on Linux");
+        OpenFiles::default()
+    }
+
+    /// The processes which has the file with the given record open
+    #[cfg(target_os = "linux")]
+    pub fn openers(&self, record: &Record) -> &[Opener] {
+        match self.files.get(&record.inode()) {
+            Some(openers) => openers,
+            None => &[],
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn openers(&self, _record: &Record) -> &[Opener] {
+        &[]
+    }
+}
+
+/// A file descriptor of a process
+#[cfg(target_os = "linux")]
+struct Fd {
+    /// The path of the file descriptor in /proc
+    path: PathBuf,
+    /// The flags of the file descriptor as listed in /proc/PID/fdinfo/FD
+    flags: Option<u32>,
+    record: Record,
+}
+
+#[cfg(target_os = "linux")]
+impl Fd {
+    fn writing(&self) -> bool {
+        match self.flags {
+            Some(flags) => flags & O_ACCMODE != O_RDONLY,
+            None => false,
+        }
+    }
+}
+
+/// Find all files that have been deleted but which are still open by any process that we are
+/// permitted to inspect. A file open several times by the same process is only listed once.
+#[cfg(target_os = "linux")]
+pub fn deleted_open_files() -> Vec<DeletedFile> {
+    let mut files: Vec<DeletedFile> = processes()
+        .into_iter()
+        .flat_map(|process: Process| deleted_files_of(process))
+        .collect();
+
+    files.sort_by_key(|f| (f.process.pid, f.inode()));
+    files.dedup_by_key(|f| (f.process.pid, f.inode()));
+    files
+}
+
+#[cfg(target_os = "linux")]
+fn deleted_files_of(process: Process) -> Vec<DeletedFile> {
+    open_fds(&process)
+        .into_iter()
+        .filter_map(|fd: Fd| {
+            let target: PathBuf = fs::read_link(&fd.path).ok()?;
+            let target: &str = target.to_str()?.strip_suffix(DELETED_SUFFIX)?;
+            Some(DeletedFile {
+                process: process.clone(),
+                path: PathBuf::from(target),
+                record: fd.record,
+            })
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn open_fds(process: &Process) -> Vec<Fd> {
+    let proc_dir: PathBuf = Path::new(PROC).join(process.pid.to_string());
+    let entries = match fs::read_dir(proc_dir.join("fd")) {
+        Ok(entries) => entries,
+        Err(err) => {
+            log::debug!(
+                "Unable to list open files of process {}: {}",
+                process.pid,
+                err
+            );
+            return Vec::with_capacity(0);
+        }
+    };
+
+    entries
+        .filter_map(|e| e.ok())
+        .filter_map(|fd: DirEntry| {
+            let record: Record = Record::stat(&fd.path(), true).ok()?;
+            let fdinfo: PathBuf = proc_dir.join("fdinfo").join(fd.file_name());
+            Some(Fd {
+                path: fd.path(),
+                flags: flags(&fdinfo),
+                record,
+            })
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn flags(fdinfo: &Path) -> Option<u32> {
+    let fdinfo: String = fs::read_to_string(fdinfo).ok()?;
+    let flags: &str = fdinfo
+        .lines()
+        .find_map(|line| line.strip_prefix("flags:"))?
+        .trim();
+    u32::from_str_radix(flags, 8).ok()
+}
+
+/// List all running processes
+#[cfg(target_os = "linux")]
+pub fn processes() -> Vec<Process> {
+    let entries = match fs::read_dir(PROC) {
+        Ok(entries) => entries,
+        Err(err) => {
+            log::error!("Unable to list processes in {}: {}", PROC, err);
+            return Vec::with_capacity(0);
+        }
+    };
+
+    entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e: DirEntry| pid(e.file_name().as_os_str()))
+        .map(|pid: u32| Process {
+            pid,
+            command: command(pid).unwrap_or_default(),
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn pid(name: &OsStr) -> Option<u32> {
+    name.to_str()?.parse().ok()
+}
+
+#[cfg(target_os = "linux")]
+fn command(pid: u32) -> io::Result<String> {
+    let comm: PathBuf = Path::new(PROC).join(pid.to_string()).join("comm");
+    Ok(fs::read_to_string(comm)?.trim_end().to_string())
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::{deleted_open_files, processes, OpenFiles, Opener};
+    use crate::record::Record;
+    use std::fs::{self, File, OpenOptions};
+    use std::io::Write;
+
+    #[test]
+    fn test_find_own_process() {
+        let own_pid: u32 = std::process::id();
+        assert!(processes().iter().any(|p| p.pid == own_pid));
+    }
+
+    #[test]
+    fn test_find_deleted_open_file() {
+        let path = std::env::temp_dir().join(format!("prn-deleted-{}", std::process::id()));
+        let mut file = File::create(&path).unwrap();
+        file.write_all(&[0u8; 1024]).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let own_pid: u32 = std::process::id();
+        let found = deleted_open_files()
+            .into_iter()
+            .find(|f| f.process.pid == own_pid && f.path == path)
+            .expect("Expected to find the deleted file");
+
+        assert_eq!(1024, found.record.size);
+        drop(file);
+    }
+
+    #[test]
+    fn test_find_opener_of_file() {
+        let path = std::env::temp_dir().join(format!("prn-open-by-{}", std::process::id()));
+        let file = File::create(&path).unwrap();
+        let reader = OpenOptions::new().read(true).open(&path).unwrap();
+
+        let open_files = OpenFiles::scan();
+        let own_pid: u32 = std::process::id();
+        let openers: Vec<&Opener> = open_files
+            .openers(&Record::from(&file.metadata().unwrap()))
+            .iter()
+            .filter(|o| o.process.pid == own_pid)
+            .collect();
+
+        fs::remove_file(&path).unwrap();
+        drop(reader);
+        assert_eq!(1, openers.len());
+        assert!(openers[0].writing);
+    }
+}
@@ -0,0 +1,300 @@
+use crate::cfg::Config;
+use crate::filesystem::fs_stats;
+use crate::mounts::{mount_of, Mount};
+use crate::print::fmt_progress;
+use crate::stats::Stats;
+use crate::walk::WalkResult;
+use itertools::Itertools;
+use lazy_static::lazy_static;
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Nothing is shown for searches which are done within this time
+const DELAY: Duration = Duration::from_secs(1);
+
+/// How often the progress line is redrawn on a terminal
+const REFRESH: Duration = Duration::from_millis(200);
+
+/// How often a line of progress is written when stderr is not a terminal
+const LOG_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The current directory is only updated for every this many entries
+const DIR_INTERVAL: u64 = 64;
+
+/// Width of the terminal when it is not known
+const DEFAULT_WIDTH: usize = 80;
+
+/// Clears the line of the terminal that the cursor is on, and moves the cursor to its start
+const CLEAR_LINE: &str = "\r\x1b[2K";
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static SHOWN: AtomicBool = AtomicBool::new(false);
+static ENTRIES: AtomicU64 = AtomicU64::new(0);
+static BYTES: AtomicU64 = AtomicU64::new(0);
+static FOUND: AtomicU64 = AtomicU64::new(0);
+
+lazy_static! {
+    static ref CURRENT_DIR: Mutex<PathBuf> = Mutex::new(PathBuf::new());
+    /// Held while the progress line is drawn or cleared, with the time it was last cleared
+    static ref TERMINAL: Mutex<Option<Instant>> = Mutex::new(None);
+}
+
+/// The progress of the search at some point in time
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub entries: u64,
+    pub dirs: u64,
+    /// Apparent size of the files walked
+    pub bytes: u64,
+    /// Number of results printed
+    pub found: u64,
+    pub elapsed: Duration,
+    /// Estimated share of the search which is done, between 0 and 1, if it is known
+    pub done: Option<f64>,
+    pub dir: PathBuf,
+}
+
+impl Snapshot {
+    /// Entries walked per second
+    pub fn rate(&self) -> f64 {
+        match self.elapsed.as_secs_f64() {
+            secs if secs > 0.0 => self.entries as f64 / secs,
+            _ => 0.0,
+        }
+    }
+
+    /// Estimated time until the search is done, assuming the rest is walked at the same pace
+    pub fn remaining(&self) -> Option<Duration> {
+        let done: f64 = self.done.filter(|done| *done >= 0.01)?;
+        Some(self.elapsed.mul_f64((1.0 - done) / done))
+    }
+}
+
+/// The used inodes and bytes of the filesystems searched, which the progress is compared to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Totals {
+    inodes: u64,
+    bytes: u64,
+}
+
+impl Totals {
+    /// The totals of the filesystems mounted at the paths searched. The share of the search which
+    /// is done can only be estimated when whole filesystems are searched, so `None` is returned
+    /// if any path is not the mount point of a filesystem.
+    fn of(paths: &[PathBuf]) -> Option<Totals> {
+        let mounts: Vec<&Mount> = paths
+            .iter()
+            .map(|path| mount_of(path).filter(|mount| mount.mount_point == *path))
+            .collect::<Option<Vec<&Mount>>>()?;
+        mounts
+            .into_iter()
+            .unique_by(|mount| &mount.mount_point)
+            .try_fold(Totals::default(), |totals, mount| {
+                let stats = fs_stats(&mount.mount_point).ok()?;
+                Some(Totals {
+                    inodes: totals.inodes + stats.inodes_used(),
+                    bytes: totals.bytes + stats.used(),
+                })
+            })
+    }
+
+    /// The larger of the shares of the used inodes and of the used bytes which have been walked,
+    /// since neither of them is exact: hard links and files in other filesystems are counted
+    /// more than once, and the apparent size of a file differs from its usage on disk
+    fn done(&self, entries: u64, bytes: u64) -> Option<f64> {
+        let share = |walked: u64, total: u64| match total {
+            0 => None,
+            total => Some(walked as f64 / total as f64),
+        };
+        let done: f64 = match (share(entries, self.inodes), share(bytes, self.bytes)) {
+            (Some(inodes), Some(bytes)) => inodes.max(bytes),
+            (inodes, bytes) => inodes.or(bytes)?,
+        };
+        Some(done.min(1.0))
+    }
+}
+
+/// Shows the progress of a search on stderr until it is dropped. On a terminal, a single line is
+/// redrawn in place, and it is cleared whenever a result or a log message is printed. Otherwise
+/// a line is written at regular intervals.
+pub struct Progress {
+    stop: Sender<()>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Progress {
+    /// Start showing the progress, if it is requested or if stderr is a terminal
+    pub fn start(cfg: &Config) -> Option<Progress> {
+        let terminal: bool = io::stderr().is_terminal();
+        if !cfg.progress && (!terminal || cfg.plumbing_mode) {
+            return None;
+        }
+        let totals: Option<Totals> = Totals::of(&cfg.paths());
+        let plumbing: bool = cfg.plumbing_mode;
+        let (stop, stopped) = mpsc::channel::<()>();
+        let start = Instant::now();
+        ENABLED.store(true, Ordering::Relaxed);
+
+        let thread = thread::spawn(move || {
+            let interval: Duration = if terminal { REFRESH } else { LOG_INTERVAL };
+            let mut wait: Duration = DELAY;
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(wait) {
+                let snapshot: Snapshot = snapshot(start.elapsed(), totals);
+                let line: String = fmt_progress(&snapshot, plumbing);
+                match terminal {
+                    true => draw(&line),
+                    false => eprintln!("{}", line),
+                }
+                wait = interval;
+            }
+        });
+        Some(Progress {
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        let _ = self.stop.send(());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        ENABLED.store(false, Ordering::Relaxed);
+        clear();
+    }
+}
+
+/// Count an entry which has been walked
+pub fn count_entry(result: &WalkResult) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let entry = match result {
+        Ok(entry) => entry,
+        Err(_) => return,
+    };
+    if !entry.file_type().is_dir() {
+        if let Ok(record) = entry.record() {
+            BYTES.fetch_add(record.size, Ordering::Relaxed);
+        }
+    }
+    if ENTRIES
+        .fetch_add(1, Ordering::Relaxed)
+        .is_multiple_of(DIR_INTERVAL)
+    {
+        if let Some(dir) = entry.path().parent() {
+            *CURRENT_DIR.lock().unwrap() = dir.to_path_buf();
+        }
+    }
+}
+
+/// Clear the progress line and count a result, which is about to be printed
+pub fn found() {
+    FOUND.fetch_add(1, Ordering::Relaxed);
+    clear();
+}
+
+/// Clear the progress line, so that something else can be printed on the terminal. It is not
+/// drawn again until it has been cleared for a while, so that it does not get in the way when
+/// results are printed in quick succession.
+pub fn clear() {
+    if !SHOWN.load(Ordering::Relaxed) && !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut cleared = TERMINAL.lock().unwrap();
+    *cleared = Some(Instant::now());
+    if SHOWN.swap(false, Ordering::Relaxed) {
+        eprint!("{}", CLEAR_LINE);
+    }
+}
+
+fn draw(line: &str) {
+    let cleared = TERMINAL.lock().unwrap();
+    if cleared.is_some_and(|cleared| cleared.elapsed() < REFRESH) {
+        return;
+    }
+    let line: String = line.chars().take(terminal_width() - 1).collect();
+    let mut stderr = io::stderr().lock();
+    let _ = write!(stderr, "{}{}", CLEAR_LINE, line);
+    let _ = stderr.flush();
+    SHOWN.store(true, Ordering::Relaxed);
+}
+
+fn snapshot(elapsed: Duration, totals: Option<Totals>) -> Snapshot {
+    let stats: Stats = Stats::get();
+    let bytes: u64 = BYTES.load(Ordering::Relaxed);
+    Snapshot {
+        entries: stats.entries,
+        dirs: stats.dirs,
+        bytes,
+        found: FOUND.load(Ordering::Relaxed),
+        elapsed,
+        done: totals.and_then(|totals| totals.done(stats.entries, bytes)),
+        dir: CURRENT_DIR.lock().unwrap().clone(),
+    }
+}
+
+#[cfg(unix)]
+fn terminal_width() -> usize {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    match unsafe { libc::ioctl(libc::STDERR_FILENO, libc::TIOCGWINSZ, &mut size) } {
+        0 if size.ws_col > 0 => size.ws_col as usize,
+        _ => DEFAULT_WIDTH,
+    }
+}
+
+#[cfg(not(unix))]
+fn terminal_width() -> usize {
+    DEFAULT_WIDTH
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Snapshot, Totals};
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    fn snapshot(done: Option<f64>) -> Snapshot {
+        Snapshot {
+            entries: 1000,
+            dirs: 10,
+            bytes: 0,
+            found: 0,
+            elapsed: Duration::from_secs(10),
+            done,
+            dir: PathBuf::from("/"),
+        }
+    }
+
+    #[test]
+    fn test_remaining_time() {
+        assert_eq!(100.0, snapshot(None).rate());
+        assert_eq!(
+            Some(Duration::from_secs(30)),
+            snapshot(Some(0.25)).remaining()
+        );
+        assert_eq!(None, snapshot(None).remaining());
+    }
+
+    #[test]
+    fn test_share_done() {
+        let totals = Totals {
+            inodes: 1000,
+            bytes: 1_000_000,
+        };
+        assert_eq!(Some(0.5), totals.done(100, 500_000));
+        assert_eq!(Some(1.0), totals.done(2000, 0));
+        let no_inodes = Totals {
+            inodes: 0,
+            bytes: 1_000_000,
+        };
+        assert_eq!(Some(0.25), no_inodes.done(100, 250_000));
+    }
+}
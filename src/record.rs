@@ -0,0 +1,219 @@
+use crate::entry_type::EntryType;
+use crate::stats;
+use crate::throttle;
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+
+/// The metadata of a file which is needed when searching, obtained with a single call to `stat`
+/// and then reused for filtering, aggregation and printing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Record {
+    /// The apparent size of the file in bytes
+    pub size: u64,
+    /// Number of 512 byte blocks allocated on disk for the file
+    pub blocks: u64,
+    pub mtime: Option<SystemTime>,
+    pub atime: Option<SystemTime>,
+    pub ctime: Option<SystemTime>,
+    /// Time of creation, which not all filesystems keep track of
+    pub btime: Option<SystemTime>,
+    pub ino: u64,
+    pub dev: u64,
+    pub nlink: u64,
+    pub uid: u32,
+    pub mode: u32,
+    /// The type of the file, unless it is a type that is not known
+    pub entry_type: Option<EntryType>,
+}
+
+impl Record {
+    /// Obtain the record for the file at `path`, or for the target of the link if `path` is a
+    /// symbolic link and `follow_link` is true
+    pub fn stat(path: &Path, follow_link: bool) -> io::Result<Record> {
+        throttle::before_stat();
+        let start = Instant::now();
+        let record: io::Result<Record> = Record::stat_once(path, follow_link);
+        stats::count_stat(start.elapsed());
+        record
+    }
+
+    fn stat_once(path: &Path, follow_link: bool) -> io::Result<Record> {
+        // Fall back to `stat` where `statx` is not available, or where it is rejected by a seccomp
+        // profile, which container runtimes often do with EPERM
+        match statx(path, follow_link) {
+            Err(err) if matches!(err.raw_os_error(), Some(ENOSYS | EPERM)) => (),
+            result => return result,
+        }
+        let metadata: Metadata = match follow_link {
+            true => fs::metadata(path)?,
+            false => fs::symlink_metadata(path)?,
+        };
+        Ok(Record::from(&metadata))
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.entry_type == Some(EntryType::File)
+    }
+
+    /// The device and inode of the file, which uniquely identifies it
+    pub fn inode(&self) -> (u64, u64) {
+        (self.dev, self.ino)
+    }
+}
+
+impl From<&Metadata> for Record {
+    #[cfg(unix)]
+    fn from(metadata: &Metadata) -> Self {
+        Record {
+            size: metadata.len(),
+            blocks: metadata.blocks(),
+            mtime: metadata.modified().ok(),
+            atime: metadata.accessed().ok(),
+            ctime: time(metadata.ctime(), metadata.ctime_nsec() as u32),
+            btime: metadata.created().ok(),
+            ino: metadata.ino(),
+            dev: metadata.dev(),
+            nlink: metadata.nlink(),
+            uid: metadata.uid(),
+            mode: metadata.mode(),
+            entry_type: EntryType::of(metadata.file_type()),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn from(metadata: &Metadata) -> Self {
+        Record {
+            size: metadata.len(),
+            blocks: (metadata.len() + 511) / 512,
+            mtime: metadata.modified().ok(),
+            atime: metadata.accessed().ok(),
+            ctime: None,
+            btime: metadata.created().ok(),
+            ino: 0,
+            dev: 0,
+            nlink: 1,
+            uid: 0,
+            mode: 0,
+            entry_type: EntryType::of(metadata.file_type()),
+        }
+    }
+}
+
+/// A point in time given as seconds and nanoseconds since the Unix epoch
+fn time(secs: i64, nanos: u32) -> Option<SystemTime> {
+    let since_epoch = Duration::new(u64::try_from(secs).ok()?, nanos);
+    UNIX_EPOCH.checked_add(since_epoch)
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+const ENOSYS: i32 = libc::ENOSYS;
+
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+const ENOSYS: i32 = 38;
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+const EPERM: i32 = libc::EPERM;
+
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+const EPERM: i32 = 1;
+
+/// Obtain the record with `statx`, which only requests the fields that are used and which also
+/// gives the time of creation of the file where it is available
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+fn statx(path: &Path, follow_link: bool) -> io::Result<Record> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let mask: libc::c_uint = libc::STATX_TYPE
+        | libc::STATX_MODE
+        | libc::STATX_NLINK
+        | libc::STATX_UID
+        | libc::STATX_INO
+        | libc::STATX_SIZE
+        | libc::STATX_BLOCKS
+        | libc::STATX_ATIME
+        | libc::STATX_MTIME
+        | libc::STATX_CTIME
+        | libc::STATX_BTIME;
+    let flags: libc::c_int = match follow_link {
+        true => libc::AT_STATX_SYNC_AS_STAT,
+        false => libc::AT_STATX_SYNC_AS_STAT | libc::AT_SYMLINK_NOFOLLOW,
+    };
+
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let mut buf: libc::statx = unsafe { std::mem::zeroed() };
+    let result: libc::c_int =
+        unsafe { libc::statx(libc::AT_FDCWD, c_path.as_ptr(), flags, mask, &mut buf) };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let timestamp = |t: libc::statx_timestamp, field: libc::c_uint| match buf.stx_mask & field {
+        0 => None,
+        _ => time(t.tv_sec, t.tv_nsec),
+    };
+    let mode: u32 = buf.stx_mode as u32;
+
+    Ok(Record {
+        size: buf.stx_size,
+        blocks: buf.stx_blocks,
+        mtime: timestamp(buf.stx_mtime, libc::STATX_MTIME),
+        atime: timestamp(buf.stx_atime, libc::STATX_ATIME),
+        ctime: timestamp(buf.stx_ctime, libc::STATX_CTIME),
+        btime: timestamp(buf.stx_btime, libc::STATX_BTIME),
+        ino: buf.stx_ino,
+        dev: libc::makedev(buf.stx_dev_major, buf.stx_dev_minor),
+        nlink: buf.stx_nlink as u64,
+        uid: buf.stx_uid,
+        mode,
+        entry_type: entry_type_of_mode(mode),
+    })
+}
+
+/// The type of a file as given by the `S_IFMT` bits of its mode
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+fn entry_type_of_mode(mode: u32) -> Option<EntryType> {
+    match mode & libc::S_IFMT {
+        libc::S_IFREG => Some(EntryType::File),
+        libc::S_IFDIR => Some(EntryType::Dir),
+        libc::S_IFLNK => Some(EntryType::Symlink),
+        libc::S_IFIFO => Some(EntryType::Fifo),
+        libc::S_IFSOCK => Some(EntryType::Socket),
+        libc::S_IFBLK => Some(EntryType::Block),
+        libc::S_IFCHR => Some(EntryType::Char),
+        _ => None,
+    }
+}
+
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+fn statx(_path: &Path, _follow_link: bool) -> io::Result<Record> {
+    Err(io::Error::from_raw_os_error(ENOSYS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Record;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_record_matches_metadata() {
+        let path = Path::new("test_dirs/file0");
+        let record: Record = Record::stat(path, false).unwrap();
+        let expected: Record = Record::from(&fs::symlink_metadata(path).unwrap());
+        assert_eq!(expected.size, record.size);
+        assert_eq!(expected.inode(), record.inode());
+        assert_eq!(expected.mtime, record.mtime);
+        assert!(record.is_file());
+    }
+
+    #[test]
+    fn test_record_of_missing_file() {
+        assert!(Record::stat(Path::new("test_dirs/missing"), false).is_err());
+    }
+}
@@ -0,0 +1,125 @@
+use lazy_static::lazy_static;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Number of entries which may be buffered from a walk that runs ahead of the consumer
+const BUFFER_SIZE: usize = 1024;
+
+/// Paths of walks which were abandoned since they did not respond in time
+pub type Incomplete = Arc<Mutex<Vec<PathBuf>>>;
+
+lazy_static! {
+    static ref INCOMPLETE: Incomplete = Incomplete::default();
+}
+
+/// Walks an iterator in a separate thread, and abandons it if no item has been produced within
+/// the timeout. This is used for remote filesystems, where a call to `stat` or `readdir` may block
+/// indefinitely when the server does not respond. A thread that is blocked in such a call can not
+/// be interrupted, so it is left behind and will be terminated when the application exits.
+pub struct TimedWalk<T> {
+    path: PathBuf,
+    timeout: Duration,
+    receiver: Option<Receiver<T>>,
+    /// Where the path is added if the walk is abandoned
+    incomplete: Incomplete,
+}
+
+impl<T: Send + 'static> TimedWalk<T> {
+    pub fn new<I>(path: &Path, walker: I, timeout: Duration, incomplete: Incomplete) -> TimedWalk<T>
+    where
+        I: Iterator<Item = T> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::sync_channel(BUFFER_SIZE);
+        thread::spawn(move || {
+            for item in walker {
+                if sender.send(item).is_err() {
+                    break;
+                }
+            }
+        });
+
+        TimedWalk {
+            path: path.to_path_buf(),
+            timeout,
+            receiver: Some(receiver),
+            incomplete,
+        }
+    }
+}
+
+impl<T> Iterator for TimedWalk<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let receiver: &Receiver<T> = self.receiver.as_ref()?;
+        match receiver.recv_timeout(self.timeout) {
+            Ok(item) => Some(item),
+            Err(RecvTimeoutError::Disconnected) => {
+                self.receiver = None;
+                None
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                log::error!(
+                    "No response from {:?} within {:?}, results for it are incomplete",
+                    self.path,
+                    self.timeout
+                );
+                self.incomplete.lock().unwrap().push(self.path.clone());
+                self.receiver = None;
+                None
+            }
+        }
+    }
+}
+
+/// Paths of remote filesystems which were abandoned since they did not respond in time
+pub fn incomplete() -> Vec<PathBuf> {
+    INCOMPLETE.lock().unwrap().clone()
+}
+
+/// Where the remote filesystems which are abandoned during the search are kept, see [incomplete]
+pub fn search_incomplete() -> Incomplete {
+    Arc::clone(&INCOMPLETE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Incomplete, TimedWalk};
+    use std::path::{Path, PathBuf};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_walk_completes_within_timeout() {
+        let incomplete = Incomplete::default();
+        let walk = TimedWalk::new(
+            Path::new("fast"),
+            0..10,
+            Duration::from_secs(5),
+            incomplete.clone(),
+        );
+        assert_eq!(10, walk.count());
+        assert!(incomplete.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_walk_is_abandoned_after_timeout() {
+        let slow = (0..10).inspect(|i| {
+            if *i == 2 {
+                thread::sleep(Duration::from_secs(2))
+            }
+        });
+        let incomplete = Incomplete::default();
+        let walk = TimedWalk::new(
+            Path::new("slow"),
+            slow,
+            Duration::from_millis(50),
+            incomplete.clone(),
+        );
+        assert_eq!(2, walk.count());
+        assert_eq!(vec![PathBuf::from("slow")], *incomplete.lock().unwrap());
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Directories with at most this many files are never sampled, and at least this many files are
+/// sampled in larger directories, so that the variance of each sample can be estimated
+pub const MIN_SAMPLED_FILES: u64 = 100;
+
+/// Number of standard deviations for a 95% confidence interval of a normal distribution
+const Z_95: f64 = 1.96;
+
+/// How the files of a directory were sampled, for an entry that was one of the sampled files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sampled {
+    /// Number of files in the directory, all of which could have been sampled
+    pub files: u64,
+    /// Number of files that were sampled
+    pub sampled: u64,
+}
+
+impl Sampled {
+    /// The number of files in the directory that each sampled file represents
+    pub fn weight(&self) -> f64 {
+        self.files as f64 / self.sampled as f64
+    }
+}
+
+/// Chooses a random sample of the files in each directory that is large enough to be sampled,
+/// while subdirectories are always kept so that every directory is still read
+#[derive(Debug, Clone, Copy)]
+pub struct Sampler {
+    /// Share of the files to sample, between 0 and 1
+    fraction: f64,
+    seed: u64,
+}
+
+impl Sampler {
+    pub fn new(percent: f64) -> Sampler {
+        let seed: u64 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        Sampler {
+            fraction: percent / 100.0,
+            seed,
+        }
+    }
+
+    /// The entries of a directory which should be stat'ed, where each file that was sampled is
+    /// given with how it was sampled
+    pub fn sample(
+        &self,
+        dir: &Path,
+        children: Vec<fs::DirEntry>,
+        follow_links: bool,
+    ) -> Vec<(fs::DirEntry, Option<Sampled>)> {
+        let is_file = |child: &fs::DirEntry| match child.file_type() {
+            Ok(t) => !(t.is_dir() || follow_links && t.is_symlink()),
+            Err(_) => false,
+        };
+        let files = children.iter().filter(|c| is_file(c)).count() as u64;
+        let sampled: u64 = self.sample_size(files);
+        if sampled == files {
+            return children.into_iter().map(|c| (c, None)).collect();
+        }
+
+        let chosen: Vec<bool> = self.choose(dir, files, sampled);
+        let sample = Sampled { files, sampled };
+        let mut file_index: usize = 0;
+        children
+            .into_iter()
+            .filter_map(|child| {
+                if !is_file(&child) {
+                    return Some((child, None));
+                }
+                file_index += 1;
+                chosen[file_index - 1].then_some((child, Some(sample)))
+            })
+            .collect()
+    }
+
+    fn sample_size(&self, files: u64) -> u64 {
+        let sampled = (files as f64 * self.fraction).ceil() as u64;
+        sampled.max(MIN_SAMPLED_FILES).min(files)
+    }
+
+    /// Choose `sampled` out of `files` files at random, by shuffling the first `sampled` positions
+    /// of the files
+    fn choose(&self, dir: &Path, files: u64, sampled: u64) -> Vec<bool> {
+        let mut rng = XorShift::new(self.seed, dir);
+        let mut order: Vec<usize> = (0..files as usize).collect();
+        for i in 0..sampled as usize {
+            let j: usize = i + (rng.next() % (files - i as u64)) as usize;
+            order.swap(i, j);
+        }
+        let mut chosen: Vec<bool> = vec![false; files as usize];
+        order[..sampled as usize]
+            .iter()
+            .for_each(|i| chosen[*i] = true);
+        chosen
+    }
+}
+
+/// A small and fast pseudo random number generator, which is more than good enough for choosing
+/// files to sample
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64, dir: &Path) -> XorShift {
+        let mut hasher = DefaultHasher::new();
+        seed.hash(&mut hasher);
+        dir.hash(&mut hasher);
+        XorShift(hasher.finish() | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// The sampled files of a single directory, from which the variance of the estimated size of the
+/// files in the directory is obtained
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Stratum {
+    files: u64,
+    sampled: u64,
+    sum: f64,
+    sum_squares: f64,
+}
+
+impl Stratum {
+    /// Add a sampled file, where `size` is 0 for a file which is not accepted by the filter
+    pub fn add(&mut self, sample: &Sampled, size: u64) {
+        let size = size as f64;
+        self.files = sample.files;
+        self.sampled += 1;
+        self.sum += size;
+        self.sum_squares += size * size;
+    }
+
+    /// Variance of the estimated total size of the files, for a simple random sample without
+    /// replacement
+    pub fn variance(&self) -> f64 {
+        if self.sampled < 2 || self.sampled >= self.files {
+            return 0.0;
+        }
+        let (n, files) = (self.sampled as f64, self.files as f64);
+        let mean: f64 = self.sum / n;
+        let sample_variance: f64 = (self.sum_squares - n * mean * mean) / (n - 1.0);
+        (files * files * (1.0 - n / files) * sample_variance / n).max(0.0)
+    }
+}
+
+/// Half the width of the 95% confidence interval of an estimate with the given variance
+pub fn margin(variance: f64) -> u64 {
+    (Z_95 * variance.sqrt()).round() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{margin, Sampled, Sampler, Stratum, MIN_SAMPLED_FILES};
+    use std::path::Path;
+
+    #[test]
+    fn test_small_directories_are_not_sampled() {
+        let sampler = Sampler::new(10.0);
+        assert_eq!(50, sampler.sample_size(50));
+        assert_eq!(MIN_SAMPLED_FILES, sampler.sample_size(500));
+        assert_eq!(1000, sampler.sample_size(10_000));
+    }
+
+    #[test]
+    fn test_choose_sample() {
+        let sampler = Sampler::new(10.0);
+        let chosen: Vec<bool> = sampler.choose(Path::new("dir"), 10_000, 1000);
+        assert_eq!(10_000, chosen.len());
+        assert_eq!(1000, chosen.iter().filter(|c| **c).count());
+    }
+
+    #[test]
+    fn test_variance_of_equal_sizes_is_zero() {
+        let sample = Sampled {
+            files: 1000,
+            sampled: 100,
+        };
+        let mut stratum = Stratum::default();
+        (0..100).for_each(|_| stratum.add(&sample, 4096));
+        assert_eq!(0, margin(stratum.variance()));
+        assert_eq!(10.0, sample.weight());
+    }
+
+    #[test]
+    fn test_variance_of_different_sizes() {
+        let sample = Sampled {
+            files: 1000,
+            sampled: 100,
+        };
+        let mut stratum = Stratum::default();
+        (0..100).for_each(|i| stratum.add(&sample, (i % 2) * 1000));
+        // Half of the sizes are 0 and half are 1000, which gives a sample variance of 500^2 * 100
+        // / 99, and the variance of the total is 1000^2 * (1 - 100/1000) * sample variance / 100
+        let sample_variance: f64 = 500.0 * 500.0 * 100.0 / 99.0;
+        let expected: f64 = 1000.0 * 1000.0 * 0.9 * sample_variance / 100.0;
+        assert!((stratum.variance() - expected).abs() < 1.0);
+    }
+}
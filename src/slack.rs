@@ -0,0 +1,155 @@
+use crate::usage::Usage;
+use std::ops::AddAssign;
+
+/// Used as block size when it cannot be obtained from the filesystem
+pub const DEFAULT_BLOCK_SIZE: u64 = 4096;
+
+/// Upper bounds of the buckets in the distribution, as a multiple of the block size divided by
+/// four. A file belongs to the first bucket whose bound is equal to or larger than its size.
+const BOUNDS_QUARTER_BLOCKS: [u64; 5] = [1, 2, 4, 16, 64];
+
+/// Number of files, their apparent size and slack for files within a certain size range
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Bucket {
+    /// Largest size, in bytes, of a file in this bucket. `None` for the bucket of the largest files
+    pub upper_bound: Option<u64>,
+    pub files: u64,
+    pub usage: Usage,
+    /// Slack of each file in the bucket added together
+    pub slack: u64,
+}
+
+/// The usage of several files combined, together with the slack of each file added together.
+/// This is not the same as the slack of the combined usage, since a sparse file allocates less
+/// than its apparent size and would otherwise hide the slack of other files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Slack {
+    pub usage: Usage,
+    pub slack: u64,
+}
+
+impl Slack {
+    pub fn of(usage: Usage) -> Slack {
+        Slack {
+            usage,
+            slack: usage.slack(),
+        }
+    }
+}
+
+impl AddAssign for Slack {
+    fn add_assign(&mut self, other: Slack) {
+        self.usage += other.usage;
+        self.slack += other.slack;
+    }
+}
+
+/// Distribution of file sizes in relation to the block size of the filesystem, which shows how
+/// much space that is lost to slack for files of different sizes.
+#[derive(Debug)]
+pub struct Distribution {
+    block_size: u64,
+    empty: Bucket,
+    buckets: Vec<Bucket>,
+}
+
+impl Distribution {
+    pub fn new(block_size: u64) -> Distribution {
+        let buckets: Vec<Bucket> = BOUNDS_QUARTER_BLOCKS
+            .iter()
+            .map(|bound| Some(bound * block_size / 4))
+            .chain(std::iter::once(None))
+            .map(|upper_bound| Bucket {
+                upper_bound,
+                ..Bucket::default()
+            })
+            .collect();
+
+        Distribution {
+            block_size,
+            empty: Bucket {
+                upper_bound: Some(0),
+                ..Bucket::default()
+            },
+            buckets,
+        }
+    }
+
+    pub fn add(&mut self, usage: Usage) {
+        let bucket: &mut Bucket = if usage.apparent == 0 {
+            &mut self.empty
+        } else {
+            self.buckets
+                .iter_mut()
+                .find(|b| match b.upper_bound {
+                    Some(bound) => usage.apparent <= bound,
+                    None => true,
+                })
+                .unwrap()
+        };
+        bucket.files += 1;
+        bucket.usage += usage;
+        bucket.slack += usage.slack();
+    }
+
+    pub fn block_size(&self) -> u64 {
+        self.block_size
+    }
+
+    pub fn buckets(&self) -> impl Iterator<Item = &Bucket> {
+        std::iter::once(&self.empty).chain(self.buckets.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bucket, Distribution, Slack};
+    use crate::usage::Usage;
+
+    fn bucket(distribution: &Distribution, upper_bound: Option<u64>) -> Bucket {
+        *distribution
+            .buckets()
+            .find(|b| b.upper_bound == upper_bound)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_empty_file() {
+        let mut distribution = Distribution::new(4096);
+        distribution.add(Usage::new(0, 0));
+        assert_eq!(1, bucket(&distribution, Some(0)).files);
+    }
+
+    #[test]
+    fn test_file_smaller_than_block() {
+        let mut distribution = Distribution::new(4096);
+        distribution.add(Usage::new(100, 4096));
+        distribution.add(Usage::new(1024, 4096));
+        let quarter_block: Bucket = bucket(&distribution, Some(1024));
+        assert_eq!(2, quarter_block.files);
+        assert_eq!(8192 - 1124, quarter_block.slack);
+    }
+
+    #[test]
+    fn test_sparse_file_does_not_hide_slack() {
+        let mut distribution = Distribution::new(4096);
+        distribution.add(Usage::new(2 * 1024 * 1024, 4096));
+        distribution.add(Usage::new(1536 * 1024, 1536 * 1024 + 4096));
+        assert_eq!(4096, bucket(&distribution, None).slack);
+    }
+
+    #[test]
+    fn test_slack_of_each_file_is_added() {
+        let mut slack = Slack::of(Usage::new(10 * 1024 * 1024, 4096));
+        slack += Slack::of(Usage::new(100, 4096));
+        assert_eq!(4096 - 100, slack.slack);
+        assert_eq!(0, slack.usage.slack());
+    }
+
+    #[test]
+    fn test_file_larger_than_largest_bound() {
+        let mut distribution = Distribution::new(4096);
+        distribution.add(Usage::new(1024 * 1024, 1024 * 1024));
+        assert_eq!(1, bucket(&distribution, None).files);
+    }
+}
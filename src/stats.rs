@@ -0,0 +1,62 @@
+use crate::walk::WalkResult;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+static ENTRIES: AtomicU64 = AtomicU64::new(0);
+static DIRS: AtomicU64 = AtomicU64::new(0);
+static ERRORS: AtomicU64 = AtomicU64::new(0);
+static STAT_CALLS: AtomicU64 = AtomicU64::new(0);
+static STAT_NANOS: AtomicU64 = AtomicU64::new(0);
+static THROTTLED_NANOS: AtomicU64 = AtomicU64::new(0);
+
+/// The work done when walking, which is counted for the whole search regardless of which walker
+/// and how many threads that were used
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub entries: u64,
+    pub dirs: u64,
+    pub errors: u64,
+    pub stat_calls: u64,
+    /// Time spent in calls to `stat`, summed over all threads
+    pub stat_time: Duration,
+    /// Time spent waiting because of the limits of the search, summed over all threads
+    pub throttled: Duration,
+}
+
+impl Stats {
+    pub fn get() -> Stats {
+        Stats {
+            entries: ENTRIES.load(Ordering::Relaxed),
+            dirs: DIRS.load(Ordering::Relaxed),
+            errors: ERRORS.load(Ordering::Relaxed),
+            stat_calls: STAT_CALLS.load(Ordering::Relaxed),
+            stat_time: Duration::from_nanos(STAT_NANOS.load(Ordering::Relaxed)),
+            throttled: Duration::from_nanos(THROTTLED_NANOS.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+pub fn count_entry(result: &WalkResult) {
+    match result {
+        Ok(entry) => {
+            ENTRIES.fetch_add(1, Ordering::Relaxed);
+            if entry.file_type().is_dir() {
+                DIRS.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        Err(_) => {
+            ERRORS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+pub fn count_stat(elapsed: Duration) {
+    let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+    STAT_CALLS.fetch_add(1, Ordering::Relaxed);
+    STAT_NANOS.fetch_add(nanos, Ordering::Relaxed);
+}
+
+pub fn count_throttled(waited: Duration) {
+    let nanos = u64::try_from(waited.as_nanos()).unwrap_or(u64::MAX);
+    THROTTLED_NANOS.fetch_add(nanos, Ordering::Relaxed);
+}
@@ -0,0 +1,220 @@
+use crate::cfg::Config;
+use crate::stats;
+use lazy_static::lazy_static;
+use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long to pause before the load average is checked again, which is also how often it is
+/// read at most
+const LOAD_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Lowest CPU priority, which is the highest niceness
+#[cfg(unix)]
+const NICEST: libc::c_int = 19;
+
+#[cfg(target_os = "linux")]
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+#[cfg(target_os = "linux")]
+const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+#[cfg(target_os = "linux")]
+const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+
+/// Whether any limit is set, so that searches without limits never have to take the lock
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref THROTTLE: Mutex<Throttle> = Mutex::new(Throttle::default());
+}
+
+/// The limits of the search, which are shared by all threads so that the rates apply to the whole
+/// search regardless of how many threads that are used
+#[derive(Debug, Default)]
+struct Throttle {
+    stats: Option<Rate>,
+    dirs: Option<Rate>,
+    max_load: Option<f64>,
+    /// When the load average was last read, and whether it was above the max load
+    load: Option<(Instant, bool)>,
+}
+
+/// Spreads calls evenly in time, by giving each call the next free slot
+#[derive(Debug)]
+struct Rate {
+    interval: Duration,
+    next: Instant,
+}
+
+impl Rate {
+    fn new(per_second: NonZeroU32) -> Rate {
+        Rate {
+            interval: Duration::from_secs(1) / per_second.get(),
+            next: Instant::now(),
+        }
+    }
+
+    /// Reserve the next free slot, and return how long to wait for it
+    fn reserve(&mut self, now: Instant) -> Duration {
+        let slot: Instant = self.next.max(now);
+        self.next = slot + self.interval;
+        slot - now
+    }
+}
+
+/// Set the limits of the search, and lower the priority of the process if requested. This must be
+/// done before any threads are started, since the priority is inherited by new threads.
+pub fn configure(cfg: &Config) {
+    if cfg.low_priority {
+        lower_priority();
+    }
+    let throttle = Throttle {
+        stats: cfg.stat_rate.map(Rate::new),
+        dirs: cfg.dir_rate.map(Rate::new),
+        max_load: cfg.max_load,
+        load: None,
+    };
+    let enabled: bool =
+        throttle.stats.is_some() || throttle.dirs.is_some() || throttle.max_load.is_some();
+    *THROTTLE.lock().unwrap() = throttle;
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Wait until the next call to `stat` is allowed
+pub fn before_stat() {
+    pause(|throttle| throttle.stats.as_mut());
+}
+
+/// Wait until the next directory is allowed to be read
+pub fn before_read_dir() {
+    pause(|throttle| throttle.dirs.as_mut());
+}
+
+fn pause<F>(rate: F)
+where
+    F: FnOnce(&mut Throttle) -> Option<&mut Rate>,
+{
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let delay: Duration = match rate(&mut THROTTLE.lock().unwrap()) {
+        Some(rate) => rate.reserve(Instant::now()),
+        None => Duration::ZERO,
+    };
+    if !delay.is_zero() {
+        thread::sleep(delay);
+    }
+    let mut throttled: Duration = delay;
+    while overloaded() {
+        thread::sleep(LOAD_INTERVAL);
+        throttled += LOAD_INTERVAL;
+    }
+    if !throttled.is_zero() {
+        stats::count_throttled(throttled);
+    }
+}
+
+/// Whether the load average over the last minute is above the max load
+fn overloaded() -> bool {
+    let mut throttle = THROTTLE.lock().unwrap();
+    let max_load: f64 = match throttle.max_load {
+        Some(max_load) => max_load,
+        None => return false,
+    };
+    match throttle.load {
+        Some((checked, overloaded)) if checked.elapsed() < LOAD_INTERVAL => overloaded,
+        _ => {
+            let load: Option<f64> = load_average();
+            let overloaded: bool = load.is_some_and(|load| load > max_load);
+            if overloaded && !matches!(throttle.load, Some((_, true))) {
+                log::info!(
+                    "Pausing while the load average {:.2} is above {}",
+                    load.unwrap_or_default(),
+                    max_load
+                );
+            }
+            throttle.load = Some((Instant::now(), overloaded));
+            overloaded
+        }
+    }
+}
+
+#[cfg(unix)]
+fn load_average() -> Option<f64> {
+    let mut load: [f64; 3] = [0.0; 3];
+    match unsafe { libc::getloadavg(load.as_mut_ptr(), 3) } {
+        n if n >= 1 => Some(load[0]),
+        _ => None,
+    }
+}
+
+#[cfg(not(unix))]
+fn load_average() -> Option<f64> {
+    None
+}
+
+/// Use the lowest CPU priority and the idle I/O class, so that the search only uses the disk when
+/// no other process needs it
+#[cfg(target_os = "linux")]
+fn lower_priority() {
+    lower_cpu_priority();
+    let ioprio: libc::c_int = IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT;
+    let result = unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, ioprio) };
+    if result == -1 {
+        log::warn!(
+            "Unable to set the I/O priority: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn lower_priority() {
+    lower_cpu_priority();
+}
+
+#[cfg(not(unix))]
+fn lower_priority() {
+    log::warn!("Lowering the priority is not supported on this platform");
+}
+
+#[cfg(unix)]
+fn lower_cpu_priority() {
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, NICEST) } == -1 {
+        log::warn!(
+            "Unable to set the CPU priority: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rate;
+    use std::num::NonZeroU32;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_calls_are_spread_evenly() {
+        let mut rate = Rate::new(NonZeroU32::new(100).unwrap());
+        let now: Instant = rate.next;
+        let delays: Vec<Duration> = (0..3).map(|_| rate.reserve(now)).collect();
+        assert_eq!(
+            vec![
+                Duration::ZERO,
+                Duration::from_millis(10),
+                Duration::from_millis(20)
+            ],
+            delays
+        );
+    }
+
+    #[test]
+    fn test_idle_time_is_not_saved_up() {
+        let mut rate = Rate::new(NonZeroU32::new(10).unwrap());
+        let later: Instant = rate.next + Duration::from_secs(5);
+        assert_eq!(Duration::ZERO, rate.reserve(later));
+        assert_eq!(Duration::from_millis(100), rate.reserve(later));
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+
+/// Index of a directory in a [DirTree]
+pub type DirId = usize;
+
+/// A directory in the tree, where the name is an index into the interned names of the tree
+#[derive(Debug)]
+struct Node<T> {
+    parent: Option<DirId>,
+    name: u32,
+    value: T,
+}
+
+/// The directories below a set of roots, each with a value of type `T`, such as the size of the
+/// files directly in it.
+///
+/// Directories are stored in an arena, where each directory refers to its parent by index and to
+/// its name by an index into a set of interned names, which are shared by all directories with the
+/// same name. A directory is always added after its parent, so values can be accumulated from
+/// children to parents in a single pass over the arena, without any recursion.
+#[derive(Debug)]
+pub struct DirTree<T> {
+    nodes: Vec<Node<T>>,
+    names: Vec<OsString>,
+    name_ids: HashMap<OsString, u32>,
+    children: HashMap<(DirId, u32), DirId>,
+    roots: Vec<(PathBuf, DirId)>,
+    /// The directory which was looked up most recently, since the files of a directory are
+    /// usually found one after another
+    last: Option<(PathBuf, DirId)>,
+}
+
+impl<T: Default> DirTree<T> {
+    pub fn new(roots: &[PathBuf]) -> DirTree<T> {
+        let mut tree = DirTree {
+            nodes: Vec::new(),
+            names: Vec::new(),
+            name_ids: HashMap::new(),
+            children: HashMap::new(),
+            roots: Vec::with_capacity(roots.len()),
+            last: None,
+        };
+        for root in roots {
+            let name: u32 = tree.intern(root.as_os_str());
+            let id: DirId = tree.add_node(None, name);
+            tree.roots.push((root.clone(), id));
+        }
+        tree
+    }
+
+    /// The value of the directory at `dir`, which is added to the tree if it is not already in
+    /// it. `None` is returned if `dir` is not below any of the roots of the tree.
+    pub fn get_mut(&mut self, dir: &Path) -> Option<&mut T> {
+        let id: DirId = self.find_or_add(dir)?;
+        Some(&mut self.nodes[id].value)
+    }
+
+    /// Merge the value of every directory into the value of its parent, where children are merged
+    /// before their parents so that each directory ends up with the total of its whole subtree
+    pub fn accumulate<F>(&mut self, merge: F)
+    where
+        F: Fn(&mut T, &T),
+    {
+        for id in (0..self.nodes.len()).rev() {
+            if let Some(parent) = self.nodes[id].parent {
+                let (head, tail) = self.nodes.split_at_mut(id);
+                merge(&mut head[parent].value, &tail[0].value);
+            }
+        }
+    }
+
+    /// The sum of the values given by `value` for each directory and all its subdirectories, indexed
+    /// by directory, without modifying the tree. The total of a directory is only added to its
+    /// parent if `add_to_parent` is true for it, which it is not if the value of the parent
+    /// already includes it.
+    pub fn totals<F, P>(&self, value: F, add_to_parent: P) -> Vec<u64>
+    where
+        F: Fn(&T) -> u64,
+        P: Fn(&T) -> bool,
+    {
+        let mut totals: Vec<u64> = self.nodes.iter().map(|n| value(&n.value)).collect();
+        for id in (0..self.nodes.len()).rev() {
+            match self.nodes[id].parent {
+                Some(parent) if add_to_parent(&self.nodes[id].value) => {
+                    totals[parent] += totals[id]
+                }
+                _ => (),
+            }
+        }
+        totals
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (DirId, &T)> {
+        self.nodes.iter().enumerate().map(|(id, n)| (id, &n.value))
+    }
+
+    /// The full path of a directory, which is only assembled when it is needed
+    pub fn path(&self, id: DirId) -> PathBuf {
+        let mut names: Vec<&OsStr> = Vec::new();
+        let mut current: Option<DirId> = Some(id);
+        while let Some(id) = current {
+            let node: &Node<T> = &self.nodes[id];
+            names.push(&self.names[node.name as usize]);
+            current = node.parent;
+        }
+        names.iter().rev().collect()
+    }
+
+    fn find_or_add(&mut self, dir: &Path) -> Option<DirId> {
+        if let Some((path, id)) = &self.last {
+            if path == dir {
+                return Some(*id);
+            }
+        }
+        let (root, root_id) = self
+            .roots
+            .iter()
+            .filter(|(root, _)| dir.starts_with(root))
+            .max_by_key(|(root, _)| root.components().count())?;
+        let relative: &Path = dir.strip_prefix(root).ok()?;
+        let mut id: DirId = *root_id;
+        for name in relative.iter() {
+            let name_id: u32 = self.intern(name);
+            id = match self.children.get(&(id, name_id)) {
+                Some(child) => *child,
+                None => self.add_child(id, name_id),
+            };
+        }
+        self.last = Some((dir.to_path_buf(), id));
+        Some(id)
+    }
+
+    fn add_node(&mut self, parent: Option<DirId>, name: u32) -> DirId {
+        self.nodes.push(Node {
+            parent,
+            name,
+            value: T::default(),
+        });
+        self.nodes.len() - 1
+    }
+
+    fn add_child(&mut self, parent: DirId, name: u32) -> DirId {
+        let id: DirId = self.add_node(Some(parent), name);
+        self.children.insert((parent, name), id);
+        id
+    }
+
+    fn intern(&mut self, name: &OsStr) -> u32 {
+        if let Some(id) = self.name_ids.get(name) {
+            return *id;
+        }
+        let id = u32::try_from(self.names.len()).expect("Too many distinct names");
+        self.names.push(name.to_os_string());
+        self.name_ids.insert(name.to_os_string(), id);
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DirTree;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_accumulate_to_ancestors() {
+        let mut tree: DirTree<u64> = DirTree::new(&[PathBuf::from("/root")]);
+        *tree.get_mut(Path::new("/root/a/b")).unwrap() += 1;
+        *tree.get_mut(Path::new("/root/a/c")).unwrap() += 2;
+        *tree.get_mut(Path::new("/root/d")).unwrap() += 4;
+        tree.accumulate(|parent, child| *parent += child);
+
+        let totals: Vec<(PathBuf, u64)> = tree.iter().map(|(id, v)| (tree.path(id), *v)).collect();
+        assert_eq!(
+            vec![
+                (PathBuf::from("/root"), 7),
+                (PathBuf::from("/root/a"), 3),
+                (PathBuf::from("/root/a/b"), 1),
+                (PathBuf::from("/root/a/c"), 2),
+                (PathBuf::from("/root/d"), 4),
+            ],
+            totals
+        );
+    }
+
+    #[test]
+    fn test_totals_leave_values_unchanged() {
+        let mut tree: DirTree<u64> = DirTree::new(&[PathBuf::from("/root")]);
+        *tree.get_mut(Path::new("/root/a")).unwrap() += 1;
+        *tree.get_mut(Path::new("/root/a/b")).unwrap() += 2;
+        assert_eq!(vec![3, 3, 2], tree.totals(|v| *v, |_| true));
+        assert_eq!(vec![0, 3, 2], tree.totals(|v| *v, |v| *v == 2));
+        assert_eq!(
+            vec![0, 1, 2],
+            tree.iter().map(|(_, v)| *v).collect::<Vec<u64>>()
+        );
+    }
+
+    #[test]
+    fn test_names_are_interned() {
+        let mut tree: DirTree<u64> = DirTree::new(&[PathBuf::from("/root")]);
+        tree.get_mut(Path::new("/root/a/src")).unwrap();
+        tree.get_mut(Path::new("/root/b/src")).unwrap();
+        assert_eq!(5, tree.nodes.len());
+        assert_eq!(4, tree.names.len());
+    }
+
+    #[test]
+    fn test_several_roots() {
+        let mut tree: DirTree<u64> =
+            DirTree::new(&[PathBuf::from("/root/a"), PathBuf::from("/root/b")]);
+        *tree.get_mut(Path::new("/root/a/x")).unwrap() += 1;
+        *tree.get_mut(Path::new("/root/b")).unwrap() += 2;
+        assert!(tree.get_mut(Path::new("/root/c")).is_none());
+        tree.accumulate(|parent, child| *parent += child);
+
+        let totals: Vec<u64> = tree.iter().map(|(_, v)| *v).collect();
+        assert_eq!(vec![1, 2, 1], totals);
+    }
+}
@@ -0,0 +1,171 @@
+use crate::record::Record;
+use crate::sample::{Sampled, Stratum};
+use std::ops::{Add, AddAssign};
+
+/// Size of the blocks reported by `st_blocks`, which is always 512 bytes regardless of the block
+/// size of the underlying filesystem.
+#[cfg(unix)]
+const BLOCK_SIZE: u64 = 512;
+
+/// Files smaller than this are never considered sparse, since small files may have their data
+/// stored inline in the inode and report no allocated blocks at all.
+const SPARSE_MIN_SIZE: u64 = 1024 * 1024;
+
+/// Which of the sizes of a file that is used for filtering, aggregation and summary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Measure {
+    /// The apparent size of a file, i.e. the number of bytes that can be read from it
+    Apparent,
+    /// The number of bytes actually allocated on disk for a file
+    Allocated,
+}
+
+/// The apparent and allocated size of a file, or of several files combined.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub apparent: u64,
+    pub allocated: u64,
+}
+
+impl Usage {
+    pub fn new(apparent: u64, allocated: u64) -> Usage {
+        Usage {
+            apparent,
+            allocated,
+        }
+    }
+
+    pub fn of(record: &Record) -> Usage {
+        Usage::new(record.size, allocated(record))
+    }
+
+    pub fn get(&self, measure: Measure) -> u64 {
+        match measure {
+            Measure::Apparent => self.apparent,
+            Measure::Allocated => self.allocated,
+        }
+    }
+
+    /// Bytes allocated on disk in excess of the apparent size, such as the unused part of the
+    /// last block of a file
+    pub fn slack(&self) -> u64 {
+        self.allocated.saturating_sub(self.apparent)
+    }
+
+    /// A file is considered sparse when less than half of its apparent size is actually
+    /// allocated on disk.
+    pub fn is_sparse(&self) -> bool {
+        self.apparent >= SPARSE_MIN_SIZE && self.allocated < self.apparent / 2
+    }
+}
+
+#[cfg(unix)]
+fn allocated(record: &Record) -> u64 {
+    record.blocks * BLOCK_SIZE
+}
+
+#[cfg(not(unix))]
+fn allocated(record: &Record) -> u64 {
+    record.size
+}
+
+impl Add for Usage {
+    type Output = Usage;
+
+    fn add(self, other: Usage) -> Usage {
+        Usage::new(
+            self.apparent + other.apparent,
+            self.allocated + other.allocated,
+        )
+    }
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, other: Usage) {
+        *self = *self + other
+    }
+}
+
+/// The combined usage of the files found in a directory and its subdirectories, and the own size
+/// of the directory itself
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DirUsage {
+    /// `None` if no files were found in the directory or any of its subdirectories
+    pub files: Option<Usage>,
+    pub own: u64,
+    /// Set once the directory and all its subdirectories have been walked, and the directory has
+    /// been included in its parent
+    pub complete: bool,
+    /// The files directly in the directory which were sampled, if only a sample was used
+    sampled: Stratum,
+    /// Variance of the estimated usage of the subdirectories
+    subdirs_variance: f64,
+}
+
+impl DirUsage {
+    pub fn add_file(&mut self, usage: Usage) {
+        *self.files.get_or_insert_with(Usage::default) += usage;
+    }
+
+    /// Add a file which was sampled, and which represents several files of the directory. The
+    /// usage is `None` for a file which is not accepted by the filter.
+    pub fn add_sampled(&mut self, usage: Option<Usage>, sample: &Sampled, measure: Measure) {
+        let size: u64 = usage.map(|u| u.get(measure)).unwrap_or(0);
+        self.sampled.add(sample, size);
+        if let Some(usage) = usage {
+            let weight: f64 = sample.weight();
+            let scale = |size: u64| (size as f64 * weight).round() as u64;
+            self.add_file(Usage::new(scale(usage.apparent), scale(usage.allocated)));
+        }
+    }
+
+    /// Include the files of a subdirectory, but not its own size
+    pub fn add_subdir(&mut self, subdir: &DirUsage) {
+        if let Some(usage) = subdir.files {
+            self.add_file(usage);
+        }
+        self.subdirs_variance += subdir.variance();
+    }
+
+    /// Variance of the estimated usage of the files, which is 0 unless files were sampled
+    pub fn variance(&self) -> f64 {
+        self.subdirs_variance + self.sampled.variance()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Measure, Usage};
+
+    #[test]
+    fn test_get_by_measure() {
+        let usage = Usage::new(100, 4096);
+        assert_eq!(100, usage.get(Measure::Apparent));
+        assert_eq!(4096, usage.get(Measure::Allocated));
+    }
+
+    #[test]
+    fn test_sparse_file() {
+        let usage = Usage::new(10 * 1024 * 1024, 4096);
+        assert!(usage.is_sparse());
+    }
+
+    #[test]
+    fn test_small_file_is_not_sparse() {
+        let usage = Usage::new(60, 0);
+        assert!(!usage.is_sparse());
+    }
+
+    #[test]
+    fn test_fully_allocated_file_is_not_sparse() {
+        let usage = Usage::new(10 * 1024 * 1024, 10 * 1024 * 1024);
+        assert!(!usage.is_sparse());
+    }
+
+    #[test]
+    fn test_add_usage() {
+        let mut usage = Usage::new(1, 512);
+        usage += Usage::new(2, 1024);
+        assert_eq!(Usage::new(3, 1536), usage);
+    }
+}
@@ -0,0 +1,337 @@
+use crate::budget;
+use crate::cache::ScanCache;
+use crate::cfg::Config;
+use crate::entry::{entry_type, Entry, Error};
+use crate::entry_type::EntryType;
+use crate::interrupt::interrupted;
+use crate::mounts::{FsTypeFilter, Pruned};
+use crate::parallel::ParallelWalk;
+use crate::progress;
+use crate::record::Record;
+use crate::remote::{search_incomplete, TimedWalk};
+use crate::sample::Sampler;
+use crate::stats;
+use crate::throttle;
+use std::collections::HashSet;
+use std::io;
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use walkdir::{DirEntry, WalkDir};
+
+pub type WalkResult = Result<Entry, Error>;
+
+/// Device and inode of files or directories which have been visited
+type Inodes = Arc<Mutex<HashSet<(u64, u64)>>>;
+
+/// Directories visited so far, identified by device and inode, which is shared between all the
+/// walkers of a search. This makes sure that a directory that can be reached in more than one way,
+/// such as through a bind mount, is only visited once. When symbolic links are followed, files are
+/// tracked as well, so that a file which is the target of several links is only counted once.
+#[derive(Debug, Clone, Default)]
+pub struct Visited {
+    dirs: Inodes,
+    files: Option<Inodes>,
+}
+
+impl Visited {
+    pub fn new(follow_links: bool) -> Visited {
+        let files = match follow_links {
+            true => Some(Arc::default()),
+            false => None,
+        };
+        Visited {
+            dirs: Arc::default(),
+            files,
+        }
+    }
+
+    pub fn first_visit(&self, entry: &Entry) -> bool {
+        self.first_visit_of(entry.path(), entry.file_type(), || entry.record())
+    }
+
+    /// Returns true if the file or directory at `path` has not been visited before. Files are
+    /// always considered to be visited for the first time, unless symbolic links are followed.
+    #[cfg(unix)]
+    fn first_visit_of<F>(&self, path: &Path, file_type: EntryType, record: F) -> bool
+    where
+        F: FnOnce() -> io::Result<Record>,
+    {
+        let visited: &Mutex<HashSet<(u64, u64)>> = match (file_type.is_dir(), &self.files) {
+            (true, _) => &self.dirs,
+            (false, Some(files)) => files,
+            (false, None) => return true,
+        };
+        match record() {
+            Ok(record) => {
+                let first: bool = visited.lock().unwrap().insert(record.inode());
+                if !first {
+                    log::info!("Skipping {:?}, which has already been visited", path);
+                }
+                first
+            }
+            Err(_) => true,
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn first_visit_of<F>(&self, _path: &Path, _file_type: EntryType, _record: F) -> bool
+    where
+        F: FnOnce() -> io::Result<Record>,
+    {
+        true
+    }
+}
+
+/// How to walk a single directory tree
+#[derive(Debug, Clone)]
+pub struct WalkOptions {
+    pub max_depth: usize,
+    pub only_local_fs: bool,
+    pub follow_links: bool,
+    /// Read the entries of each directory in inode order, see [ParallelWalk]
+    pub inode_order: bool,
+    /// Only stat a sample of the files in large directories
+    pub sampler: Option<Sampler>,
+    /// Read the directories which look the largest first, see [ParallelWalk]
+    pub biggest_first: bool,
+    /// Produce the entry of each directory after all the entries below it, see [ParallelWalk]
+    pub contents_first: bool,
+    /// Paths which should not be walked, see [Pruned]
+    pub pruned: Arc<HashSet<PathBuf>>,
+    pub visited: Visited,
+    /// Cached entries of directories from previous searches, see [ScanCache]
+    pub cache: Option<Arc<ScanCache>>,
+    /// Keep track of the directories which have not been completely read, so that they can be
+    /// listed if the search is stopped, see [budget::unvisited]
+    pub track_unvisited: bool,
+}
+
+/// Walk all the paths of the configuration, where each file and directory is only visited once
+pub fn walk(cfg: &Config) -> impl Iterator<Item = WalkResult> + '_ {
+    walk_cached(cfg, None)
+}
+
+/// Walk all the paths of the configuration, where directories which have not been modified since
+/// they were cached are not read again. The walk is stopped if the search is interrupted or if its
+/// budget is exhausted, and paths which were not reached are then counted as unvisited.
+pub fn walk_cached(
+    cfg: &Config,
+    cache: Option<Arc<ScanCache>>,
+) -> impl Iterator<Item = WalkResult> + '_ {
+    let visited = Visited::new(cfg.follow_links);
+    cfg.paths()
+        .into_iter()
+        .flat_map(
+            move |path: PathBuf| -> Box<dyn Iterator<Item = WalkResult>> {
+                if stopped() {
+                    if cfg.has_budget() {
+                        budget::add_unvisited(iter::once(path));
+                    }
+                    return Box::new(iter::empty());
+                }
+                walk_path(cfg, &path, visited.clone(), cache.clone())
+            },
+        )
+        .inspect(stats::count_entry)
+        .inspect(progress::count_entry)
+}
+
+/// Whether the search should stop, since it has been interrupted or its budget is exhausted
+pub fn stopped() -> bool {
+    interrupted() || budget::exhausted()
+}
+
+/// Whether the search was stopped before it was done, without checking the budget again
+pub fn was_stopped() -> bool {
+    interrupted() || budget::reason().is_some()
+}
+
+/// The paths of all the entries walked, sorted so that walks in different orders can be compared
+#[cfg(test)]
+pub fn walked_paths(cfg: &Config) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = walk(cfg)
+        .filter_map(|e| e.ok())
+        .map(|e| e.path().to_path_buf())
+        .collect();
+    paths.sort();
+    paths
+}
+
+#[cfg(test)]
+pub fn create_walker(cfg: &Config, path: &Path) -> impl IntoIterator<Item = WalkResult> {
+    walk_path(cfg, path, Visited::new(cfg.follow_links), None)
+}
+
+fn walk_path(
+    cfg: &Config,
+    path: &Path,
+    visited: Visited,
+    cache: Option<Arc<ScanCache>>,
+) -> Box<dyn Iterator<Item = WalkResult>> {
+    let fs_types: FsTypeFilter = cfg.into();
+    let pruned: Pruned = fs_types.pruned(path);
+    let options = WalkOptions {
+        max_depth: cfg.max_depth(),
+        only_local_fs: cfg.only_local_fs(),
+        follow_links: cfg.follow_links,
+        inode_order: cfg.inode_order,
+        sampler: cfg.sample.map(Sampler::new),
+        biggest_first: cfg.biggest_first,
+        contents_first: cfg.contents_first(),
+        pruned: Arc::new(pruned.paths.clone()),
+        visited,
+        cache,
+        track_unvisited: cfg.has_budget(),
+    };
+    let threads: usize = cfg.threads();
+    let root_walker = walk_filesystem(path, options.clone(), threads);
+    let root_walker: Box<dyn Iterator<Item = WalkResult>> = match fs_types.is_remote(path) {
+        true => Box::new(TimedWalk::new(
+            path,
+            root_walker,
+            cfg.remote_timeout,
+            search_incomplete(),
+        )),
+        false => root_walker,
+    };
+    let root_walker = root_walker.take_while(|_| !stopped());
+
+    // Remote filesystems are never crossed into when staying on the same filesystem
+    let remote: Vec<PathBuf> = match cfg.only_local_fs() {
+        true => Vec::with_capacity(0),
+        false => pruned.remote,
+    };
+
+    let root: PathBuf = path.to_path_buf();
+    let timeout: Duration = cfg.remote_timeout;
+    let contents_first: bool = options.contents_first;
+    let remote_walkers = remote.into_iter().filter_map(move |mount: PathBuf| {
+        let depth: usize = mount.components().count() - root.components().count();
+        if depth > options.max_depth {
+            return None;
+        }
+        if stopped() {
+            if options.track_unvisited {
+                budget::add_unvisited(iter::once(mount));
+            }
+            return None;
+        }
+        let mut pruned: HashSet<PathBuf> = pruned.paths.clone();
+        pruned.remove(&mount);
+        let options = WalkOptions {
+            max_depth: options.max_depth - depth,
+            only_local_fs: false,
+            pruned: Arc::new(pruned),
+            ..options.clone()
+        };
+        let walker = walk_filesystem(&mount, options, threads);
+        Some(
+            TimedWalk::new(&mount, walker, timeout, search_incomplete()).take_while(|_| !stopped()),
+        )
+    });
+
+    // When the contents of directories come first, remote filesystems are walked before the tree
+    // they are mounted in, so that the directories they are mounted below come after them
+    match contents_first {
+        true => Box::new(remote_walkers.flatten().chain(root_walker)),
+        false => Box::new(root_walker.chain(remote_walkers.flatten())),
+    }
+}
+
+fn walk_filesystem(
+    path: &Path,
+    options: WalkOptions,
+    threads: usize,
+) -> Box<dyn Iterator<Item = WalkResult> + Send> {
+    // Only the parallel walker can read entries in inode order, sample files, use the cache, read
+    // the largest directories first, produce the contents of directories first or keep track of
+    // the directories not read yet, which it also does with one thread. The contents first mode of
+    // walkdir can not be used, since it applies `filter_entry` only after a directory has been
+    // descended into.
+    let parallel: bool = options.inode_order
+        || options.sampler.is_some()
+        || options.cache.is_some()
+        || options.biggest_first
+        || options.contents_first
+        || options.track_unvisited;
+    if threads > 1 || parallel {
+        log::debug!("Walking {:?} with {} threads: {:?}", path, threads, options);
+        return Box::new(ParallelWalk::new(path, options, threads));
+    }
+
+    let follow_links: bool = options.follow_links;
+    let walker = WalkDir::new(path)
+        .follow_links(follow_links)
+        .max_depth(options.max_depth)
+        .same_file_system(options.only_local_fs);
+
+    // The record obtained when filtering an entry, which is given to the entry so that it is not
+    // stat'ed again. An entry which is kept is produced right after it has been filtered.
+    let filtered: Arc<Mutex<Option<Record>>> = Arc::new(Mutex::new(None));
+    let recorded: Arc<Mutex<Option<Record>>> = Arc::clone(&filtered);
+
+    log::debug!("walkdir: {:?}", walker);
+    let walker = walker
+        .into_iter()
+        .filter_entry(move |e: &DirEntry| {
+            let mut record: Option<Record> = None;
+            let keep: bool = !options.pruned.contains(e.path())
+                && options
+                    .visited
+                    .first_visit_of(e.path(), entry_type(e.file_type()), || {
+                        let result = Record::stat(e.path(), follow_links);
+                        record = result.as_ref().ok().copied();
+                        result
+                    });
+            *filtered.lock().unwrap() = record;
+            // A directory which is kept is read next, so this is where reading it is throttled
+            if keep && e.file_type().is_dir() {
+                throttle::before_read_dir();
+            }
+            keep
+        })
+        .map(move |e| match e {
+            Ok(entry) => {
+                let record: Option<Record> = recorded.lock().unwrap().take();
+                Ok(Entry::from_walkdir(entry, follow_links, record))
+            }
+            Err(err) => Err(Error::from(err)),
+        })
+        .inspect(log_loop);
+
+    Box::new(walker)
+}
+
+/// A symbolic link which points to one of its own ancestors is not followed, since that would
+/// result in an infinite loop
+fn log_loop(entry: &WalkResult) {
+    if let Err(err) = entry {
+        if let (Some(path), Some(ancestor)) = (err.path(), err.loop_ancestor()) {
+            log::info!(
+                "Not following {:?}, which links back to {:?}",
+                path,
+                ancestor
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::walk;
+    use crate::cfg::Config;
+
+    const TEST_DIR: &str = "test_dirs";
+
+    #[test]
+    fn test_overlapping_paths_are_visited_once() {
+        let cfg = Config::default()
+            .with_path(TEST_DIR)
+            .with_path("test_dirs/sub_dir")
+            .with_path("./test_dirs");
+        let entries: usize = walk(&cfg).filter_map(|e| e.ok()).count();
+        assert_eq!(5, entries);
+    }
+}